@@ -10,34 +10,60 @@ use std::f64::consts::PI;
 
 pub const FULL_M: usize = 2000;
 pub const FULL_N: usize = 1200;
-const HALF_M_INT: usize = FULL_M / 2;
-const HALF_M: f64 = HALF_M_INT as f64;
-const HALF_N: f64 = (FULL_N / 2) as f64;
-const HALF_N_PLUS_ONE: f64 = HALF_N + 1.;
+/// Steepness of the formula's step functions, e.g. `e(-e(-SHARPNESS * x))`.
+/// Equal to half of `FULL_M`, but fixed regardless of the output resolution.
+const SHARPNESS_INT: usize = 1000;
+const SHARPNESS: f64 = SHARPNESS_INT as f64;
 
 pub struct Artwork;
 
 impl Art for Artwork {
+    const NAME: &'static str = "strawberries";
+
+    const TITLE: &'static str = "Strawberries";
+
     const FULL_M: usize = FULL_M;
 
     const FULL_N: usize = FULL_N;
 
-    fn draw(m: f64, n: f64) -> (u8, u8, u8) {
-        draw(m, n)
+    const FUNCTIONS: &'static [Function] = functions![
+        H(v),
+        L(v, s),
+        C(v, s),
+        B(s),
+        A(v, s),
+        U(s),
+        M(s),
+        N(s),
+        R(t, s),
+        E(t, s),
+        P(s),
+        Q(s),
+        W(),
+    ];
+
+    fn draw_xy(x: f64, y: f64) -> (u8, u8, u8) {
+        draw_xy(x, y)
+    }
+
+    fn channels_xy(x: f64, y: f64) -> (f64, f64, f64) {
+        channels_xy(x, y)
     }
 }
 
 #[inline(always)]
-pub fn draw(m: f64, n: f64) -> (u8, u8, u8) {
-    let result = rgb(
-        F(H(0, (m - HALF_M) / HALF_N, (HALF_N_PLUS_ONE - n) / HALF_N)),
-        F(H(1, (m - HALF_M) / HALF_N, (HALF_N_PLUS_ONE - n) / HALF_N)),
-        F(H(2, (m - HALF_M) / HALF_N, (HALF_N_PLUS_ONE - n) / HALF_N)),
-    );
+pub fn draw_xy(x: f64, y: f64) -> (u8, u8, u8) {
+    let (r, g, b) = channels_xy(x, y);
+    let result = rgb(r, g, b);
 
     result
 }
 
+#[inline(always)]
+pub fn channels_xy(x: f64, y: f64) -> (f64, f64, f64) {
+    (F(H(0, x, y)), F(H(1, x, y)), F(H(2, x, y)))
+}
+
 track! {
     #[inline(always)]
     pub fn rgb(r: f64, g: f64, b: f64) -> (u8, u8, u8) {
@@ -48,8 +74,8 @@ track! {
 
 track! {
     pub fn F(x: f64) -> f64 {
-        let term0 = 255. * e(-e(-HALF_M * x));
-        let term1 = abs(x).powf(e(-e(HALF_M * (x - 1.))));
+        let term0 = 255. * e(-e(-SHARPNESS * x));
+        let term1 = abs(x).powf(e(-e(SHARPNESS * (x - 1.))));
         let result = term0 * term1;
         result
     }
@@ -63,8 +89,8 @@ track! {
             let term0 = product_with_key("H", 0, s - 1, x, y, |r, x, y| {
                 let r_ = r as f64;
 
-                let term010 = 1. - A(HALF_M_INT, r, x, y);
-                let term0110 = e(-e(-HALF_M * (r_ - 1. / 2.)));
+                let term010 = 1. - A(SHARPNESS_INT, r, x, y);
+                let term0110 = e(-e(-SHARPNESS * (r_ - 1. / 2.)));
                 let term0111 = U(r, x, y);
                 let term011 = 1. - term0110 * term0111;
                 let term0120 = 5. / 4.;
@@ -81,7 +107,7 @@ track! {
             let term110 = 71. / 10. - 10. * P(s, x, y);
             let term11 = e(-e(-abs(term110)));
             let term12 = U(s, x, y);
-            let term13 = A(HALF_M_INT, s, x, y);
+            let term13 = A(SHARPNESS_INT, s, x, y);
             let term14 = 1. - U(s, x, y);
             let term15 = L(v, s, x, y);
             let term1 = term10 * term11 * term12 + term13 * term14 * term15;
@@ -111,7 +137,7 @@ memo_many! {
         let term13 = 20. * e(-e(-10. * term130));
         let term1 = term10 + term11 + term12 + term13;
         let term2 = A(4, s, x, y);
-        let term3 = A(HALF_M_INT, s, x, y);
+        let term3 = A(SHARPNESS_INT, s, x, y);
         let term4 = B(s, x, y);
         let result = term0 * term1 * term2 * term3 + term4;
         result
@@ -178,7 +204,7 @@ memo_many! {
         let term20 = Q(s, x, y);
         let term21 = P(s, x, y);
         let term2 = term20.pow2() + term21.pow2() - 1.;
-        let result = e(-e(-HALF_M * term0) - e(v_ * term1) - e(v_ * term2));
+        let result = e(-e(-SHARPNESS * term0) - e(v_ * term1) - e(v_ * term2));
         result
     }
 }
@@ -212,7 +238,7 @@ memo_many! {
         let term11 = 18. / 25.;
         let term12 = 3. / 2. * Q(s, x, y);
         let term1 = term10 - term11 - term12.pow8();
-        let result = e(-e(-100. * term0) - e(HALF_M * term1));
+        let result = e(-e(-100. * term0) - e(SHARPNESS * term1));
 
         result
     }
@@ -237,7 +263,7 @@ memo_many! {
         let term11 = 71. / 100.;
         let term12 = 3. / 2. * Q(s, x, y);
         let term1 = term10 - term11 - term12.pow8();
-        let result = e(-e(100. * term0) - e(-HALF_M * term1));
+        let result = e(-e(100. * term0) - e(-SHARPNESS * term1));
         result
     }
 }
@@ -248,7 +274,7 @@ memo_many! {
         let term0 = E(t, s, x, y);
         let term100 = E(t, s, x, y);
         let term10 = abs(term100) - 1.;
-        let term1 = e(-e(HALF_M * term10));
+        let term1 = e(-e(SHARPNESS * term10));
         let result = term0 * term1;
         result
     }
@@ -258,7 +284,7 @@ memo_many! {
     /// E(t,s,x,y) called with t = [0, 1], s = 0..=30
     pub fn E(t: usize, s: usize, x: f64, y: f64) -> f64 {
         let t_ = t as f64;
-        let term0 = HALF_M / sqrt(20.);
+        let term0 = SHARPNESS / sqrt(20.);
         let term1 = Q(s, x, y);
         let term200 = 1. - 2. * t_;
         let term201 = P(s, x, y);
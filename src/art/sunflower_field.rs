@@ -10,32 +10,66 @@ use std::f64::consts::{FRAC_1_PI, PI};
 
 pub const FULL_M: usize = 2000;
 pub const FULL_N: usize = 1200;
-const HALF_M: f64 = (FULL_M / 2) as f64;
-const HALF_N: f64 = (FULL_N / 2) as f64;
-const HALF_N_PLUS_ONE: f64 = HALF_N + 1.;
+/// Steepness of the formula's step functions, e.g. `e(-e(-SHARPNESS * x))`.
+/// Equal to half of `FULL_M`, but fixed regardless of the output resolution.
+const SHARPNESS: f64 = 1000.;
+/// Weight of the `arccos(cos(10 Q))` terms in `N`.
+/// Equal to half of `FULL_N`, but fixed regardless of the output resolution.
+const SHARPNESS_N: f64 = 600.;
 
 pub struct Artwork;
 
 impl Art for Artwork {
+    const NAME: &'static str = "sunflower_field";
+
+    const TITLE: &'static str = "Sunflower Field";
+
     const FULL_M: usize = FULL_M;
 
     const FULL_N: usize = FULL_N;
 
-    fn draw(m: f64, n: f64) -> (u8, u8, u8) {
-        draw(m, n)
+    const FUNCTIONS: &'static [Function] = functions![
+        H(v),
+        A(v),
+        V(s),
+        W(v, s),
+        W1(s),
+        W2(s),
+        K(s),
+        C(s),
+        L(s),
+        Q(s),
+        P(s),
+        B(v),
+        U(s),
+        E(),
+        R(v, s),
+        J(v, s),
+        N(s),
+        T(s),
+    ];
+
+    fn draw_xy(x: f64, y: f64) -> (u8, u8, u8) {
+        draw_xy(x, y)
+    }
+
+    fn channels_xy(x: f64, y: f64) -> (f64, f64, f64) {
+        channels_xy(x, y)
     }
 }
 
 #[inline(always)]
-pub fn draw(m: f64, n: f64) -> (u8, u8, u8) {
-    let result = rgb(
-        F(H(0, (m - HALF_M) / HALF_N, (HALF_N_PLUS_ONE - n) / HALF_N)),
-        F(H(1, (m - HALF_M) / HALF_N, (HALF_N_PLUS_ONE - n) / HALF_N)),
-        F(H(2, (m - HALF_M) / HALF_N, (HALF_N_PLUS_ONE - n) / HALF_N)),
-    );
+pub fn draw_xy(x: f64, y: f64) -> (u8, u8, u8) {
+    let (r, g, b) = channels_xy(x, y);
+    let result = rgb(r, g, b);
     result
 }
 
+#[inline(always)]
+pub fn channels_xy(x: f64, y: f64) -> (f64, f64, f64) {
+    (F(H(0, x, y)), F(H(1, x, y)), F(H(2, x, y)))
+}
+
 track! {
     #[inline(always)]
     pub fn rgb(r: f64, g: f64, b: f64) -> (u8, u8, u8) {
@@ -46,8 +80,8 @@ track! {
 
 track! {
     pub fn F(x: f64) -> f64 {
-        let term0 = 255. * e(-e(-HALF_M * x));
-        let term1 = abs(x).powf(e(-e(HALF_M * (x - 1.))));
+        let term0 = 255. * e(-e(-SHARPNESS * x));
+        let term1 = abs(x).powf(e(-e(SHARPNESS * (x - 1.))));
         let result = term0 * term1;
         result
     }
@@ -283,10 +317,10 @@ memo_many! {
 memo_many! {
     pub fn L(s: usize, x: f64, y: f64) -> f64 {
         let term0num0 = 10. * P(s, x, y);
-        let term0num = HALF_M * arccos(cos(term0num0));
+        let term0num = SHARPNESS * arccos(cos(term0num0));
         let term0den00 = 10. * Q(s, x, y);
         let term0den0 = arccos(cos(term0den00));
-        let term0den = 1. + HALF_M * abs(term0den0);
+        let term0den = 1. + SHARPNESS * abs(term0den0);
         let result = arctan(term0num / term0den);
         result
     }
@@ -444,7 +478,7 @@ memo_many! {
         let term04 = arccos(cos(term040));
         let term050 = arccos(cos(10. * Q(s, x, y)));
         let term05 = 1. - FRAC_1_PI * term050;
-        let term0 = term00 * cos(term01) - term02 - term03 + HALF_N * term04 + HALF_N * term05.pow10();
+        let term0 = term00 * cos(term01) - term02 - term03 + SHARPNESS_N * term04 + SHARPNESS_N * term05.pow10();
         let term10 = 200.;
         let term110 = P(s, x, y);
         let term111 = 1. / 10.;
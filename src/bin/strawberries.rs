@@ -1,5 +0,0 @@
-use naderi_yeganeh_art::*;
-
-fn main() {
-    run::<art::strawberries::Artwork>();
-}
@@ -1,5 +0,0 @@
-use naderi_yeganeh_art::*;
-
-fn main() {
-    run::<art::sunflower_field::Artwork>();
-}
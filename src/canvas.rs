@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use crate::Art;
+
+/// Size of a rendered image in pixels, which need not match the artwork's native
+/// `FULL_M` x `FULL_N`. Pixels are mapped onto the native `(m, n)` coordinates,
+/// so the formula is evaluated at the same `(x, y)` range at any resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "canvas should not be empty");
+        Self { width, height }
+    }
+
+    /// The artwork's own `FULL_M` x `FULL_N` canvas
+    pub fn native<Artwork: Art>() -> Self {
+        Self::new(Artwork::FULL_M, Artwork::FULL_N)
+    }
+
+    /// The native canvas scaled by `scale`, e.g. `0.1` for a thumbnail or `4.` for a poster
+    pub fn scaled<Artwork: Art>(scale: f64) -> Self {
+        Self::new(
+            ((Artwork::FULL_M as f64 * scale).round() as usize).max(1),
+            ((Artwork::FULL_N as f64 * scale).round() as usize).max(1),
+        )
+    }
+
+    /// Scales the native canvas to `width`, keeping its aspect ratio
+    pub fn with_width<Artwork: Art>(width: usize) -> Self {
+        let height = width as f64 * Artwork::FULL_N as f64 / Artwork::FULL_M as f64;
+        Self::new(width, (height.round() as usize).max(1))
+    }
+
+    /// Scales the native canvas to `height`, keeping its aspect ratio
+    pub fn with_height<Artwork: Art>(height: usize) -> Self {
+        let width = height as f64 * Artwork::FULL_M as f64 / Artwork::FULL_N as f64;
+        Self::new((width.round() as usize).max(1), height)
+    }
+
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Native `(m, n)` at the point `(px, py)` of this canvas, where `(0., 0.)` is the
+    /// top-left corner of the first pixel, and `(0.5, 0.5)` is its centre.
+    /// On the native canvas, the centre of pixel `(x, y)` maps to `(x + 1, y + 1)`.
+    pub fn mn<Artwork: Art>(&self, px: f64, py: f64) -> (f64, f64) {
+        let m = px * Artwork::FULL_M as f64 / self.width as f64 + 0.5;
+        let n = py * Artwork::FULL_N as f64 / self.height as f64 + 0.5;
+        (m, n)
+    }
+
+    /// Inverse of [`Canvas::mn`]
+    pub fn point<Artwork: Art>(&self, m: f64, n: f64) -> (f64, f64) {
+        let px = (m - 0.5) * self.width as f64 / Artwork::FULL_M as f64;
+        let py = (n - 0.5) * self.height as f64 / Artwork::FULL_N as f64;
+        (px, py)
+    }
+}
+
+/// A rectangle of canvas pixels, e.g. a tile or a region of interest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Whether `other` lies entirely within this rect.
+    /// Compares without adding, so that a huge `other` cannot overflow.
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x - self.x <= self.width
+            && other.y - self.y <= self.height
+            && other.width <= self.width - (other.x - self.x)
+            && other.height <= self.height - (other.y - self.y)
+    }
+
+    /// Canvas coordinates of every pixel, row by row
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        (y..y + height).flat_map(move |y| (x..x + width).map(move |x| (x, y)))
+    }
+}
+
+impl FromStr for Rect {
+    type Err = String;
+
+    /// Parses `x,y,width,height`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(',')
+            .map(|part| part.trim().parse::<usize>())
+            .collect::<Result<Vec<_>, _>>();
+        match parts.as_deref() {
+            Ok(&[x, y, width, height]) => Ok(Rect::new(x, y, width, height)),
+            _ => Err(format!("expected x,y,width,height, found: {s}")),
+        }
+    }
+}
+
+impl Canvas {
+    /// The whole canvas as a [`Rect`]
+    pub fn rect(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    /// Splits the canvas into square tiles of `tile_size`, row by row.
+    /// Tiles on the right and bottom edges are cut short.
+    pub fn tiles(&self, tile_size: usize) -> Vec<Rect> {
+        assert!(tile_size > 0, "tiles should not be empty");
+        (0..self.height)
+            .step_by(tile_size)
+            .flat_map(|y| {
+                (0..self.width).step_by(tile_size).map(move |x| {
+                    Rect::new(
+                        x,
+                        y,
+                        tile_size.min(self.width - x),
+                        tile_size.min(self.height - y),
+                    )
+                })
+            })
+            .collect()
+    }
+}
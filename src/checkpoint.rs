@@ -0,0 +1,198 @@
+//! On-disk progress for long tiled renders, so that they can resume after being interrupted.
+//!
+//! A checkpoint directory holds one raw RGB file per completed tile, plus a `manifest.txt`
+//! recording the render settings and which tiles are complete. Tiles are buffered in memory
+//! and written out every [`Checkpoint::interval`], with the manifest written last, so a tile is
+//! only ever listed once its file is on disk.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{Art, RenderOptions};
+
+const MANIFEST: &str = "manifest.txt";
+
+/// Settings that must match for a checkpoint to be resumed
+#[derive(Debug, Clone, PartialEq)]
+struct Settings {
+    artwork: String,
+    width: usize,
+    height: usize,
+    sampling: String,
+    tile_size: usize,
+}
+
+impl Settings {
+    fn new<Artwork: Art>(options: &RenderOptions, tile_size: usize) -> Self {
+        Self {
+            artwork: Artwork::NAME.to_string(),
+            width: options.canvas.width,
+            height: options.canvas.height,
+            sampling: options.sampling.to_string(),
+            tile_size,
+        }
+    }
+}
+
+pub struct Checkpoint {
+    dir: PathBuf,
+    settings: Settings,
+    done: BTreeSet<usize>,
+    /// Completed tiles not yet on disk, as raw RGB bytes
+    pending: Vec<(usize, Vec<u8>)>,
+    last_saved: Instant,
+    /// How often completed tiles are written to disk
+    pub interval: Duration,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint in `dir`, creating it if it does not exist yet.
+    /// Fails if the directory holds a checkpoint for different settings.
+    pub fn open<Artwork: Art>(
+        dir: impl Into<PathBuf>,
+        options: &RenderOptions,
+        tile_size: usize,
+    ) -> io::Result<Self> {
+        let dir = dir.into();
+        let settings = Settings::new::<Artwork>(options, tile_size);
+        let done = match fs::read_to_string(dir.join(MANIFEST)) {
+            Ok(manifest) => {
+                let (saved, done) = parse_manifest(&manifest)?;
+                if saved != settings {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "checkpoint in {dir} was made with different settings: {saved:?}",
+                            dir = dir.display()
+                        ),
+                    ));
+                }
+                done
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                fs::create_dir_all(&dir)?;
+                BTreeSet::new()
+            }
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            dir,
+            settings,
+            done,
+            pending: Vec::new(),
+            last_saved: Instant::now(),
+            interval: Duration::from_secs(10),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Indices of the tiles already on disk
+    pub fn done(&self) -> impl Iterator<Item = usize> + '_ {
+        self.done.iter().copied()
+    }
+
+    pub fn is_done(&self, tile: usize) -> bool {
+        self.done.contains(&tile)
+    }
+
+    /// Reads a completed tile, which should have `len` pixels
+    pub fn load(&self, tile: usize, len: usize) -> io::Result<Vec<(u8, u8, u8)>> {
+        let bytes = fs::read(self.tile_path(tile))?;
+        if bytes.len() != len * 3 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("tile {tile} should have {len} pixels"),
+            ));
+        }
+        Ok(bytes
+            .chunks_exact(3)
+            .map(|rgb| (rgb[0], rgb[1], rgb[2]))
+            .collect())
+    }
+
+    /// Records a completed tile, saving to disk if [`Checkpoint::interval`] has passed
+    pub fn complete(&mut self, tile: usize, pixels: &[(u8, u8, u8)]) -> io::Result<()> {
+        let bytes = pixels.iter().flat_map(|&(r, g, b)| [r, g, b]).collect();
+        self.pending.push((tile, bytes));
+        if self.last_saved.elapsed() >= self.interval {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Writes any pending tiles and then the manifest
+    pub fn save(&mut self) -> io::Result<()> {
+        for (tile, bytes) in &self.pending {
+            fs::write(self.tile_path(*tile), bytes)?;
+        }
+        self.done
+            .extend(self.pending.drain(..).map(|(tile, _)| tile));
+
+        let Settings {
+            artwork,
+            width,
+            height,
+            sampling,
+            tile_size,
+        } = &self.settings;
+        let done = self
+            .done
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let manifest = format!(
+            "artwork = {artwork}\nwidth = {width}\nheight = {height}\nsampling = {sampling}\ntile_size = {tile_size}\ndone = {done}\n"
+        );
+        // replace the manifest atomically, so an interrupted save cannot corrupt it
+        let tmp = self.dir.join(format!("{MANIFEST}.tmp"));
+        fs::write(&tmp, manifest)?;
+        fs::rename(tmp, self.dir.join(MANIFEST))?;
+
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+
+    fn tile_path(&self, tile: usize) -> PathBuf {
+        self.dir.join(format!("tile-{tile}.rgb"))
+    }
+}
+
+fn parse_manifest(manifest: &str) -> io::Result<(Settings, BTreeSet<usize>)> {
+    let invalid =
+        |what: &str| io::Error::new(ErrorKind::InvalidData, format!("{MANIFEST}: {what}"));
+    let field = |key: &str| {
+        manifest
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(k, _)| k.trim() == key)
+            .map(|(_, value)| value.trim())
+            .ok_or_else(|| invalid(&format!("missing {key}")))
+    };
+    let number = |key: &str| {
+        field(key)?
+            .parse::<usize>()
+            .map_err(|_| invalid(&format!("invalid {key}")))
+    };
+
+    let settings = Settings {
+        artwork: field("artwork")?.to_string(),
+        width: number("width")?,
+        height: number("height")?,
+        sampling: field("sampling")?.to_string(),
+        tile_size: number("tile_size")?,
+    };
+    let done = field("done")?
+        .split_whitespace()
+        .map(|tile| tile.parse().map_err(|_| invalid("invalid done")))
+        .collect::<io::Result<_>>()?;
+    Ok((settings, done))
+}
@@ -0,0 +1,201 @@
+//! Pixel by pixel comparison of two renders, e.g. to check that optimising a formula left the
+//! picture unchanged
+
+use std::fmt;
+
+use crate::{image::Image, render::UNRENDERED, rgb_from_softbuffer_color, softbuffer_color, Rect};
+
+/// Brightness of the smallest difference in [`Diff::amplify`], so that even a difference of one
+/// level stands out against the black of identical pixels
+const MIN_VISIBLE: f64 = 64.;
+
+/// Differing pixels listed in the terminal, by the `diff` command and when a render compared
+/// with a reference finishes in the viewer
+pub const LISTED_DIFFERENCES: usize = 20;
+
+/// A pixel whose colour differs between the two renders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DifferingPixel {
+    pub x: usize,
+    pub y: usize,
+    pub a: (u8, u8, u8),
+    pub b: (u8, u8, u8),
+}
+
+impl DifferingPixel {
+    /// Largest difference across the channels
+    pub fn error(&self) -> u8 {
+        let (a, b) = (self.a, self.b);
+        a.0.abs_diff(b.0)
+            .max(a.1.abs_diff(b.1))
+            .max(a.2.abs_diff(b.2))
+    }
+}
+
+impl fmt::Display for DifferingPixel {
+    /// e.g. `(12, 34): (255, 0, 0) vs (254, 0, 0)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "({x}, {y}): {a:?} vs {b:?}",
+            x = self.x,
+            y = self.y,
+            a = self.a,
+            b = self.b
+        )
+    }
+}
+
+/// How two renders of the same size differ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    pub width: usize,
+    pub height: usize,
+    /// Absolute difference of each channel of each pixel, row by row,
+    /// or `None` where a pixel was not compared because it is not rendered yet
+    pub errors: Vec<Option<(u8, u8, u8)>>,
+    /// In row order, with coordinates in the canvas
+    pub differing: Vec<DifferingPixel>,
+    /// Pixels compared, i.e. those of `errors` that are not `None`
+    pub compared: usize,
+    /// Largest difference of any channel of any pixel
+    pub max_error: u8,
+    /// Mean absolute difference of the channels of the pixels compared
+    pub mean_error: f64,
+}
+
+impl Diff {
+    /// Compares the pixels of `a` and `b`, which should be the same size
+    pub fn new(a: &Image, b: &Image) -> Self {
+        assert_eq!(
+            (a.width, a.height),
+            (b.width, b.height),
+            "the images should be the same size"
+        );
+        let pixels = a.pixels.iter().zip(&b.pixels);
+        Self::from_pairs(
+            a.width,
+            a.height,
+            (0, 0),
+            pixels.map(|(&a, &b)| Some((a, b))),
+        )
+    }
+
+    /// Compares `image`, the partly rendered `rect` of a canvas in the viewer's format, with the
+    /// same part of `reference`, a render of the whole canvas, leaving out [`UNRENDERED`] pixels
+    pub fn rendered(image: &[u32], rect: Rect, reference: &Image) -> Self {
+        let pixels = rect.pixels().zip(image).map(|((x, y), &color)| {
+            (color != UNRENDERED).then(|| {
+                let reference = reference.pixels[y * reference.width + x];
+                (rgb_from_softbuffer_color(color), reference)
+            })
+        });
+        Self::from_pairs(rect.width, rect.height, (rect.x, rect.y), pixels)
+    }
+
+    /// Compares the pairs of colours of a `width` x `height` rect at `(x, y)` in the canvas,
+    /// row by row
+    fn from_pairs(
+        width: usize,
+        height: usize,
+        (x, y): (usize, usize),
+        pairs: impl Iterator<Item = Option<((u8, u8, u8), (u8, u8, u8))>>,
+    ) -> Self {
+        let mut errors = Vec::with_capacity(width * height);
+        let mut differing = Vec::new();
+        let (mut compared, mut max_error, mut total) = (0, 0, 0);
+        for (index, pair) in pairs.enumerate() {
+            let Some((a, b)) = pair else {
+                errors.push(None);
+                continue;
+            };
+            let error = (a.0.abs_diff(b.0), a.1.abs_diff(b.1), a.2.abs_diff(b.2));
+            errors.push(Some(error));
+            compared += 1;
+            if a != b {
+                let pixel = DifferingPixel {
+                    x: x + index % width.max(1),
+                    y: y + index / width.max(1),
+                    a,
+                    b,
+                };
+                max_error = max_error.max(pixel.error());
+                total += error.0 as u64 + error.1 as u64 + error.2 as u64;
+                differing.push(pixel);
+            }
+        }
+        Self {
+            width,
+            height,
+            errors,
+            differing,
+            compared,
+            max_error,
+            mean_error: if compared > 0 {
+                total as f64 / (3 * compared) as f64
+            } else {
+                0.
+            },
+        }
+    }
+
+    /// Factor that brings the largest difference to full brightness
+    pub fn gain(&self) -> f64 {
+        255. / self.max_error.max(1) as f64
+    }
+
+    /// Colour of a pixel whose channels differ by `error`, multiplied by `gain`,
+    /// black if there is no difference
+    pub fn amplify(error: (u8, u8, u8), gain: f64) -> (u8, u8, u8) {
+        let channel = |error: u8| match error {
+            0 => 0,
+            error => (error as f64 * gain).clamp(MIN_VISIBLE, 255.).round() as u8,
+        };
+        (channel(error.0), channel(error.1), channel(error.2))
+    }
+
+    /// The differences multiplied by `gain`, in the viewer's format, with pixels that were not
+    /// compared left [`UNRENDERED`]
+    pub fn to_softbuffer(&self, gain: f64) -> Vec<u32> {
+        self.errors
+            .iter()
+            .map(|&error| match error {
+                Some(error) => softbuffer_color(Self::amplify(error, gain)),
+                None => UNRENDERED,
+            })
+            .collect()
+    }
+
+    /// The differences multiplied by `gain`, with pixels that were not compared marked with
+    /// [`Image::UNRENDERED_MARKER`]
+    pub fn to_image(&self, gain: f64) -> Image {
+        Image::from_softbuffer(self.width, self.height, &self.to_softbuffer(gain))
+    }
+
+    /// Prints the summary and up to `limit` of the differing pixels, the largest differences first
+    pub fn print(&self, limit: usize) {
+        println!("{self}");
+        let mut largest = self.differing.clone();
+        largest.sort_by_key(|pixel| std::cmp::Reverse(pixel.error()));
+        for pixel in largest.iter().take(limit) {
+            println!("  {pixel}");
+        }
+        if largest.len() > limit {
+            println!("  ... and {more} more", more = largest.len() - limit);
+        }
+    }
+}
+
+impl fmt::Display for Diff {
+    /// e.g. `12 of 2400000 pixels differ, max error 3, mean error 0.0000`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{differing} of {compared} pixels differ, max error {max}, mean error {mean:.4}",
+            differing = self.differing.len(),
+            compared = self.compared,
+            max = self.max_error,
+            mean = self.mean_error
+        )
+    }
+}
@@ -0,0 +1,45 @@
+//! Self-contained image file encoders, and decoders for the formats that are simplest to read
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::image::Image;
+
+pub mod bmp;
+pub mod pfm;
+pub mod png;
+pub mod ppm;
+pub mod qoi;
+pub mod tga;
+
+/// Writes an 8-bit RGB [`Image`] in a particular file format
+pub trait Encoder: Sync {
+    /// File extensions for this format, in lowercase and without the leading `.`
+    fn extensions(&self) -> &'static [&'static str];
+
+    fn encode(&self, w: &mut dyn Write, image: &Image) -> io::Result<()>;
+}
+
+/// Every available encoder, in order of preference
+pub const ENCODERS: &[&dyn Encoder] = &[&png::Png, &qoi::Qoi, &bmp::Bmp, &ppm::Ppm, &tga::Tga];
+
+/// The encoder whose extension matches that of `path`, ignoring case
+pub fn for_path(path: &Path) -> Option<&'static dyn Encoder> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    ENCODERS
+        .iter()
+        .copied()
+        .find(|encoder| encoder.extensions().contains(&extension.as_str()))
+}
+
+/// Extensions of the formats that [`Image::load`] reads
+pub const READABLE: &[&str] = &["ppm", "qoi"];
+
+/// Every supported extension, e.g. for error messages
+pub fn extensions() -> impl Iterator<Item = &'static str> {
+    ENCODERS
+        .iter()
+        .flat_map(|encoder| encoder.extensions().iter().copied())
+}
@@ -0,0 +1,62 @@
+//! 24-bit uncompressed Windows bitmap encoder.
+//! See <https://learn.microsoft.com/en-us/windows/win32/gdi/bitmap-storage>
+
+use std::io::{self, Write};
+
+use crate::image::Image;
+
+use super::Encoder;
+
+pub struct Bmp;
+
+impl Encoder for Bmp {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["bmp"]
+    }
+
+    fn encode(&self, mut w: &mut dyn Write, image: &Image) -> io::Result<()> {
+        write(&mut w, image)
+    }
+}
+
+const FILE_HEADER_LEN: u32 = 14;
+const INFO_HEADER_LEN: u32 = 40;
+
+pub fn write(w: &mut impl Write, image: &Image) -> io::Result<()> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "image is too large for BMP");
+    let width = i32::try_from(image.width).map_err(|_| too_large())?;
+    let height = i32::try_from(image.height).map_err(|_| too_large())?;
+    // rows are padded to a multiple of 4 bytes
+    let stride = (image.width * 3).next_multiple_of(4);
+    let data_len = u32::try_from(stride * image.height).map_err(|_| too_large())?;
+    let offset = FILE_HEADER_LEN + INFO_HEADER_LEN;
+    let file_len = offset.checked_add(data_len).ok_or_else(too_large)?;
+
+    // BITMAPFILEHEADER
+    w.write_all(b"BM")?;
+    w.write_all(&file_len.to_le_bytes())?;
+    w.write_all(&[0; 4])?; // reserved
+    w.write_all(&offset.to_le_bytes())?;
+
+    // BITMAPINFOHEADER
+    w.write_all(&INFO_HEADER_LEN.to_le_bytes())?;
+    w.write_all(&width.to_le_bytes())?;
+    w.write_all(&height.to_le_bytes())?; // positive, so rows are stored from the bottom up
+    w.write_all(&1u16.to_le_bytes())?; // colour planes
+    w.write_all(&24u16.to_le_bytes())?; // bits per pixel
+    w.write_all(&0u32.to_le_bytes())?; // compression: none
+    w.write_all(&data_len.to_le_bytes())?;
+    w.write_all(&2835i32.to_le_bytes())?; // horizontal resolution: 72 DPI
+    w.write_all(&2835i32.to_le_bytes())?; // vertical resolution: 72 DPI
+    w.write_all(&0u32.to_le_bytes())?; // palette colours
+    w.write_all(&0u32.to_le_bytes())?; // important colours
+
+    let mut line = Vec::with_capacity(stride);
+    for row in image.pixels.chunks(image.width.max(1)).rev() {
+        line.clear();
+        line.extend(row.iter().flat_map(|&(r, g, b)| [b, g, r]));
+        line.resize(stride, 0);
+        w.write_all(&line)?;
+    }
+    w.flush()
+}
@@ -0,0 +1,28 @@
+//! Portable Float Map encoder, for inspecting channel values before quantisation.
+//! See <https://www.pauldebevec.com/Research/HDR/PFM/>
+//!
+//! Channel values are divided by 255, so `1.0` is full intensity, and anything the
+//! artwork's `rgb` would clip shows up as a value above `1.0` or below `0.0`.
+
+use std::io::{self, Write};
+
+use crate::image::FloatImage;
+
+pub fn write(w: &mut impl Write, image: &FloatImage) -> io::Result<()> {
+    // a negative scale marks the samples as little-endian
+    write!(
+        w,
+        "PF\n{width} {height}\n-1.0\n",
+        width = image.width,
+        height = image.height
+    )?;
+    // rows are stored from the bottom up
+    for row in image.pixels.chunks(image.width.max(1)).rev() {
+        for &(r, g, b) in row {
+            for c in [r, g, b] {
+                w.write_all(&((c / 255.) as f32).to_le_bytes())?;
+            }
+        }
+    }
+    w.flush()
+}
@@ -0,0 +1,520 @@
+//! Minimal PNG encoder: 8-bit RGB, adaptive row filters, and a zlib stream
+//! compressed with fixed-Huffman deflate.
+//! See <https://www.w3.org/TR/png/> and <https://www.rfc-editor.org/rfc/rfc1951>
+
+use std::io::{self, Write};
+
+use crate::image::Image;
+
+use super::Encoder;
+
+pub struct Png;
+
+impl Encoder for Png {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["png"]
+    }
+
+    fn encode(&self, mut w: &mut dyn Write, image: &Image) -> io::Result<()> {
+        write(&mut w, image)
+    }
+}
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Bytes per pixel for 8-bit RGB
+const BPP: usize = 3;
+
+pub fn write(w: &mut impl Write, image: &Image) -> io::Result<()> {
+    let width = u32::try_from(image.width).map_err(|_| too_large())?;
+    let height = u32::try_from(image.height).map_err(|_| too_large())?;
+
+    w.write_all(&SIGNATURE)?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[
+        8, // bit depth
+        2, // colour type: truecolour
+        0, // compression method: deflate
+        0, // filter method: adaptive
+        0, // interlace method: none
+    ]);
+    write_chunk(w, b"IHDR", &ihdr)?;
+
+    let filtered = filter(image);
+    write_chunk(w, b"IDAT", &zlib(&filtered))?;
+
+    write_chunk(w, b"IEND", &[])
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "image is too large for PNG")
+}
+
+fn write_chunk(w: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| too_large())?;
+    w.write_all(&len.to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = crc32_update(crc32_update(!0, kind), data);
+    w.write_all(&(!crc).to_be_bytes())
+}
+
+/// Prefixes every scanline with the filter type that minimises the sum of
+/// absolute differences, the heuristic recommended by the PNG specification
+fn filter(image: &Image) -> Vec<u8> {
+    let stride = image.width * BPP;
+    let mut out = Vec::with_capacity((stride + 1) * image.height);
+    let mut prev = vec![0u8; stride];
+    let mut line = Vec::with_capacity(stride);
+    let mut candidate = vec![0u8; stride];
+    let mut best = vec![0u8; stride];
+
+    for row in image.pixels.chunks(image.width.max(1)) {
+        line.clear();
+        line.extend(row.iter().flat_map(|&(r, g, b)| [r, g, b]));
+
+        let mut best_kind = 0;
+        let mut best_score = u64::MAX;
+        for kind in 0..5 {
+            for i in 0..stride {
+                let a = if i >= BPP { line[i - BPP] } else { 0 };
+                let b = prev[i];
+                let c = if i >= BPP { prev[i - BPP] } else { 0 };
+                let predictor = match kind {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                candidate[i] = line[i].wrapping_sub(predictor);
+            }
+            let score = candidate
+                .iter()
+                .map(|&v| (v as i8).unsigned_abs() as u64)
+                .sum::<u64>();
+            if score < best_score {
+                best_score = score;
+                best_kind = kind;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+
+        out.push(best_kind);
+        out.extend_from_slice(&best);
+        std::mem::swap(&mut prev, &mut line);
+    }
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn crc32_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut n = 0;
+        while n < 256 {
+            let mut c = n as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                k += 1;
+            }
+            table[n] = c;
+            n += 1;
+        }
+        table
+    };
+    for &byte in bytes {
+        crc = TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest run that cannot overflow `b` before reduction
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // CMF: deflate with a 32K window, FLG: no dictionary, fastest level
+    bits.out.extend_from_slice(&[0x78, 0x01]);
+    deflate(&mut bits, data);
+    let mut out = bits.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    len: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `count` bits of `value`, least significant bit first
+    fn bits(&mut self, value: u32, count: u32) {
+        self.acc |= (value as u64) << self.len;
+        self.len += count;
+        while self.len >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is packed most significant bit first
+    fn code(&mut self, code: u32, count: u32) {
+        self.bits(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+const WINDOW: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Single final block with the fixed Huffman tables and greedy LZ77 matching
+fn deflate(bits: &mut BitWriter, data: &[u8]) {
+    bits.bits(1, 1); // BFINAL
+    bits.bits(1, 2); // BTYPE = fixed Huffman
+
+    let hash = |i: usize| {
+        let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    };
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |i: usize, head: &mut Vec<usize>, prev: &mut Vec<usize>| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            prev[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[i..i + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            write_match(bits, best_len, best_dist);
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            write_literal(bits, data[i] as u32);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+
+    write_literal(bits, 256); // end of block
+}
+
+fn write_literal(bits: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => bits.code(0x30 + symbol, 8),
+        144..=255 => bits.code(0x190 + symbol - 144, 9),
+        256..=279 => bits.code(symbol - 256, 7),
+        _ => bits.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(bits: &mut BitWriter, len: usize, dist: usize) {
+    let code = LENGTH_BASE.partition_point(|&base| base as usize <= len) - 1;
+    write_literal(bits, 257 + code as u32);
+    bits.bits(
+        (len - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code] as u32,
+    );
+
+    let code = DIST_BASE.partition_point(|&base| base as usize <= dist) - 1;
+    bits.code(code as u32, 5);
+    bits.bits(
+        (dist - DIST_BASE[code] as usize) as u32,
+        DIST_EXTRA[code] as u32,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads bits least significant first, as [`BitWriter`] writes them
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        at: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |value, i| {
+                let bit = self.bytes[self.at / 8] >> (self.at % 8) & 1;
+                self.at += 1;
+                value | (bit as u32) << i
+            })
+        }
+
+        /// Reads a Huffman code, most significant bit first
+        fn code(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |code, _| code << 1 | self.bits(1))
+        }
+
+        /// Reads a literal or length symbol with the fixed Huffman codes
+        fn symbol(&mut self) -> u32 {
+            let mut code = self.code(7);
+            if code <= 0x17 {
+                return code + 256;
+            }
+            code = code << 1 | self.bits(1);
+            match code {
+                0x30..=0xbf => code - 0x30,
+                0xc0..=0xc7 => code - 0xc0 + 280,
+                _ => (code << 1 | self.bits(1)) - 0x190 + 144,
+            }
+        }
+    }
+
+    /// Decompresses a zlib stream of fixed Huffman blocks, checking its header and checksum
+    fn inflate(zlib: &[u8]) -> Vec<u8> {
+        assert_eq!(
+            (zlib[0] as u16 * 256 + zlib[1] as u16) % 31,
+            0,
+            "header check"
+        );
+        assert_eq!(zlib[0] & 0x0f, 8, "deflate");
+        let mut bits = BitReader {
+            bytes: &zlib[2..zlib.len() - 4],
+            at: 0,
+        };
+        let mut out = Vec::<u8>::new();
+        loop {
+            let last = bits.bits(1) == 1;
+            assert_eq!(bits.bits(2), 1, "fixed Huffman block");
+            loop {
+                let symbol = bits.symbol();
+                match symbol {
+                    0..=255 => out.push(symbol as u8),
+                    256 => break,
+                    _ => {
+                        let code = (symbol - 257) as usize;
+                        let len = LENGTH_BASE[code] as usize
+                            + bits.bits(LENGTH_EXTRA[code] as u32) as usize;
+                        let code = bits.code(5) as usize;
+                        let dist =
+                            DIST_BASE[code] as usize + bits.bits(DIST_EXTRA[code] as u32) as usize;
+                        for _ in 0..len {
+                            out.push(out[out.len() - dist]);
+                        }
+                    }
+                }
+            }
+            if last {
+                break;
+            }
+        }
+        assert_eq!(bits.at.div_ceil(8), bits.bytes.len(), "trailing data");
+        let adler = u32::from_be_bytes(zlib[zlib.len() - 4..].try_into().unwrap());
+        assert_eq!(adler, adler32(&out));
+        out
+    }
+
+    /// Decodes a PNG as written by [`write`], checking every chunk's CRC
+    fn decode(png: &[u8]) -> Image {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut chunks = Vec::new();
+        let mut at = 8;
+        while at < png.len() {
+            let len = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            let (kind, data) = (&png[at + 4..at + 8], &png[at + 8..at + 8 + len]);
+            let crc = u32::from_be_bytes(png[at + 8 + len..at + 12 + len].try_into().unwrap());
+            assert_eq!(crc, !crc32_update(crc32_update(!0, kind), data));
+            chunks.push((kind, data));
+            at += 12 + len;
+        }
+        let kinds = chunks.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+
+        let ihdr = chunks[0].1;
+        let width = u32::from_be_bytes(ihdr[0..4].try_into().unwrap()) as usize;
+        let height = u32::from_be_bytes(ihdr[4..8].try_into().unwrap()) as usize;
+        assert_eq!(ihdr[8..], [8, 2, 0, 0, 0]);
+
+        let filtered = inflate(chunks[1].1);
+        let stride = width * BPP;
+        assert_eq!(filtered.len(), (stride + 1) * height);
+        let mut prev = vec![0u8; stride];
+        let mut pixels = Vec::new();
+        for row in filtered.chunks(stride + 1) {
+            let mut line = row[1..].to_vec();
+            for i in 0..stride {
+                let a = if i >= BPP { line[i - BPP] } else { 0 };
+                let (b, c) = (prev[i], if i >= BPP { prev[i - BPP] } else { 0 });
+                let predictor = match row[0] {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    4 => paeth(a, b, c),
+                    kind => panic!("unknown filter type {kind}"),
+                };
+                line[i] = line[i].wrapping_add(predictor);
+            }
+            pixels.extend(line.chunks(BPP).map(|rgb| (rgb[0], rgb[1], rgb[2])));
+            prev = line;
+        }
+        Image::new(width, height, pixels)
+    }
+
+    fn encode(image: &Image) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write(&mut bytes, image).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn checksums() {
+        // the check values from the CRC catalogue and RFC 1950's examples
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            !crc32_update(crc32_update(!0, b"1234"), b"56789"),
+            0xcbf4_3926
+        );
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // long enough to need reducing in between
+        assert_eq!(adler32(&[0xff; 100_000]), 0x149a_302c);
+    }
+
+    /// Checked against Python's `zlib` and `binascii.crc32`
+    #[test]
+    fn golden_pixel() {
+        let png = encode(&Image::new(1, 1, vec![(255, 0, 0)]));
+        assert_eq!(
+            png,
+            [
+                0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, // signature
+                0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52, // IHDR
+                0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90,
+                0x77, 0x53, 0xde, // CRC
+                0x00, 0x00, 0x00, 0x0c, 0x49, 0x44, 0x41, 0x54, // IDAT
+                0x78, 0x01, // zlib header
+                0x63, 0xf8, 0xcf, 0xc0, 0x00, 0x00, // filter type 0, then 255, 0, 0
+                0x03, 0x01, 0x01, 0x00, // Adler-32
+                0xdb, 0x7d, 0x2e, 0xbc, // CRC
+                0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, // IEND
+                0xae, 0x42, 0x60, 0x82, // CRC
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        // runs long enough for the longest matches, noise for literals of both code lengths,
+        // and rows repeating rows far back for long distances
+        let (width, height) = (300, 40);
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                match y % 10 {
+                    0..3 => (10, 20, 30),
+                    3..6 => ((x * 97 + y * 13) as u8, (x * 31) as u8, (y * 71 + x) as u8),
+                    _ => ((x * 2) as u8, (y * 3) as u8, 200),
+                }
+            })
+            .collect();
+        let image = Image::new(width, height, pixels);
+        let decoded = decode(&encode(&image));
+        assert_eq!((decoded.width, decoded.height), (width, height));
+        assert_eq!(decoded.pixels, image.pixels);
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        let decoded = decode(&encode(&Image::new(0, 0, Vec::new())));
+        assert_eq!((decoded.width, decoded.height), (0, 0));
+    }
+}
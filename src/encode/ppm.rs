@@ -0,0 +1,179 @@
+//! Binary Portable Pixmap encoder and decoder, trivially readable by most tools.
+//! See <https://netpbm.sourceforge.net/doc/ppm.html>
+
+use std::io::{self, Read, Write};
+
+use crate::image::Image;
+
+use super::Encoder;
+
+pub struct Ppm;
+
+impl Encoder for Ppm {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ppm"]
+    }
+
+    fn encode(&self, mut w: &mut dyn Write, image: &Image) -> io::Result<()> {
+        write(&mut w, image)
+    }
+}
+
+pub fn write(w: &mut impl Write, image: &Image) -> io::Result<()> {
+    write!(
+        w,
+        "P6\n{width} {height}\n255\n",
+        width = image.width,
+        height = image.height
+    )?;
+    let bytes = image
+        .pixels
+        .iter()
+        .flat_map(|&(r, g, b)| [r, g, b])
+        .collect::<Vec<_>>();
+    w.write_all(&bytes)?;
+    w.flush()
+}
+
+/// Reads a binary Portable Pixmap with 8-bit channels
+pub fn read(r: &mut impl Read) -> io::Result<Image> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    // the magic number, width, height and maximum value, separated by whitespace and comments
+    let mut fields = Vec::new();
+    let mut at = 0;
+    while fields.len() < 4 {
+        match bytes.get(at) {
+            None => return Err(invalid("truncated PPM header")),
+            Some(b'#') => {
+                while bytes.get(at).is_some_and(|&byte| byte != b'\n') {
+                    at += 1;
+                }
+            }
+            Some(byte) if byte.is_ascii_whitespace() => at += 1,
+            Some(_) => {
+                let start = at;
+                while bytes
+                    .get(at)
+                    .is_some_and(|byte| !byte.is_ascii_whitespace())
+                {
+                    at += 1;
+                }
+                fields.push(&bytes[start..at]);
+            }
+        }
+    }
+    // a single whitespace byte separates the header from the pixels
+    at += 1;
+
+    if fields[0] != b"P6" {
+        return Err(invalid("expected a binary PPM, starting with P6"));
+    }
+    let number = |field: &[u8]| {
+        std::str::from_utf8(field)
+            .ok()
+            .and_then(|field| field.parse::<usize>().ok())
+            .ok_or_else(|| invalid("expected a number in the PPM header"))
+    };
+    let (width, height) = (number(fields[1])?, number(fields[2])?);
+    if number(fields[3])? != 255 {
+        return Err(invalid(
+            "only PPMs with a maximum value of 255 are supported",
+        ));
+    }
+    let len = width
+        .checked_mul(height)
+        .and_then(|len| len.checked_mul(3))
+        .ok_or_else(|| invalid("PPM dimensions too large"))?;
+    let pixels = bytes
+        .get(at..)
+        .and_then(|data| data.get(..len))
+        .ok_or_else(|| invalid("truncated PPM pixels"))?
+        .chunks_exact(3)
+        .map(|rgb| (rgb[0], rgb[1], rgb[2]))
+        .collect();
+    Ok(Image::new(width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> io::Result<Image> {
+        read(&mut &bytes[..])
+    }
+
+    fn assert_invalid(bytes: &[u8]) {
+        let error = decode(bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{error}");
+    }
+
+    #[test]
+    fn writes_a_header_then_the_channels() {
+        let image = Image::new(3, 1, vec![(1, 2, 3), (4, 5, 6), (7, 8, 9)]);
+        let mut bytes = Vec::new();
+        write(&mut bytes, &image).unwrap();
+        assert_eq!(bytes, b"P6\n3 1\n255\n\x01\x02\x03\x04\x05\x06\x07\x08\x09");
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 1));
+        assert_eq!(decoded.pixels, image.pixels);
+    }
+
+    #[test]
+    fn reads_pixels_that_look_like_the_header() {
+        // whitespace and `#` after the single separator are channel values
+        let image = Image::new(1, 2, vec![(b'\n', b'#', b' '), (b'\t', b'\r', b'2')]);
+        let mut bytes = Vec::new();
+        write(&mut bytes, &image).unwrap();
+        assert_eq!(decode(&bytes).unwrap().pixels, image.pixels);
+    }
+
+    #[test]
+    fn reads_header_whitespace_and_comments() {
+        for bytes in [
+            &b"P6 1 1 255 \x01\x02\x03"[..],
+            b"P6\t1\r1\n255\t\x01\x02\x03",
+            b"P6\n\n  1   1\n255\n\x01\x02\x03",
+            b"P6 # a comment\n1 1\n# another\n255\n\x01\x02\x03",
+            b"P6\n1 # width\n1 # height\n255\n\x01\x02\x03",
+        ] {
+            let decoded = decode(bytes).unwrap();
+            assert_eq!((decoded.width, decoded.height), (1, 1));
+            assert_eq!(decoded.pixels, vec![(1, 2, 3)]);
+        }
+    }
+
+    #[test]
+    fn rejects_other_maximum_values() {
+        assert_invalid(b"P6\n1 1\n15\n\x01\x02\x03");
+        assert_invalid(b"P6\n1 1\n65535\n\x00\x01\x00\x02\x00\x03");
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        // plain text pixmaps, graymaps and bitmaps
+        assert_invalid(b"P3\n1 1\n255\n1 2 3\n");
+        assert_invalid(b"P5\n1 1\n255\n\x01");
+        assert_invalid(b"P4\n8 1\n\x01");
+        assert_invalid(b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        assert_invalid(b"");
+        assert_invalid(b"P6\n1 1");
+        assert_invalid(b"P6\n1 # 1 255\n");
+        assert_invalid(b"P6\n-1 1\n255\n");
+        assert_invalid(b"P6\n1 x\n255\n");
+    }
+
+    #[test]
+    fn rejects_dimensions_larger_than_the_file() {
+        assert_invalid(b"P6\n2 2\n255\n\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b");
+        assert_invalid(format!("P6\n{max} {max}\n255\n", max = usize::MAX).as_bytes());
+        // width * height fits, but not once multiplied by the three channels
+        assert_invalid(format!("P6\n{width} 1\n255\n", width = usize::MAX / 2).as_bytes());
+    }
+}
@@ -0,0 +1,227 @@
+//! "Quite OK Image" encoder and decoder, a fast lossless format for intermediate files.
+//! See <https://qoiformat.org/qoi-specification.pdf>
+
+use std::io::{self, Read, Write};
+
+use crate::image::Image;
+
+use super::Encoder;
+
+pub struct Qoi;
+
+impl Encoder for Qoi {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["qoi"]
+    }
+
+    fn encode(&self, mut w: &mut dyn Write, image: &Image) -> io::Result<()> {
+        write(&mut w, image)
+    }
+}
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+/// Mask of the 2-bit tags of the ops other than [`OP_RGB`] and [`OP_RGBA`]
+const MASK: u8 = 0xc0;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+const MAX_RUN: u8 = 62;
+
+pub fn write(w: &mut impl Write, image: &Image) -> io::Result<()> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "image is too large for QOI");
+    let width = u32::try_from(image.width).map_err(|_| too_large())?;
+    let height = u32::try_from(image.height).map_err(|_| too_large())?;
+
+    let mut out = Vec::with_capacity(14 + image.pixels.len() * 2 + END_MARKER.len());
+    out.extend_from_slice(b"qoif");
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    out.push(3); // channels: RGB
+    out.push(0); // colour space: sRGB with linear alpha
+
+    // every pixel is opaque, so alpha never changes from its initial 255
+    let hash = |(r, g, b): (u8, u8, u8)| {
+        (r as usize * 3 + g as usize * 5 + b as usize * 7 + 255 * 11) % 64
+    };
+    let mut index = [None; 64];
+    let mut prev = (0u8, 0u8, 0u8);
+    let mut run = 0u8;
+
+    for &pixel in &image.pixels {
+        if pixel == prev {
+            run += 1;
+            if run == MAX_RUN {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+
+        let slot = hash(pixel);
+        if index[slot] == Some(pixel) {
+            out.push(OP_INDEX | slot as u8);
+        } else {
+            index[slot] = Some(pixel);
+
+            let dr = pixel.0.wrapping_sub(prev.0) as i8;
+            let dg = pixel.1.wrapping_sub(prev.1) as i8;
+            let db = pixel.2.wrapping_sub(prev.2) as i8;
+            let dr_dg = dr.wrapping_sub(dg);
+            let db_dg = db.wrapping_sub(dg);
+
+            if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+                out.push(OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8);
+            } else if (-32..=31).contains(&dg)
+                && (-8..=7).contains(&dr_dg)
+                && (-8..=7).contains(&db_dg)
+            {
+                out.push(OP_LUMA | (dg + 32) as u8);
+                out.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+            } else {
+                out.extend_from_slice(&[OP_RGB, pixel.0, pixel.1, pixel.2]);
+            }
+        }
+        prev = pixel;
+    }
+    if run > 0 {
+        out.push(OP_RUN | (run - 1));
+    }
+    out.extend_from_slice(&END_MARKER);
+
+    w.write_all(&out)?;
+    w.flush()
+}
+
+/// Reads an image with 3 or 4 channels, ignoring any alpha
+pub fn read(r: &mut impl Read) -> io::Result<Image> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    if bytes.len() < 14 || &bytes[..4] != b"qoif" {
+        return Err(invalid("expected a QOI image, starting with qoif"));
+    }
+    let dimension = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+    let (width, height) = (dimension(4), dimension(8));
+    // each byte of data encodes at most a run of MAX_RUN pixels, so a file claiming more is
+    // broken, and checking first keeps a bad header from asking for a huge allocation
+    let len = width
+        .checked_mul(height)
+        .filter(|&len| len <= (bytes.len() - 14).saturating_mul(MAX_RUN as usize))
+        .ok_or_else(|| invalid("QOI dimensions too large for the data"))?;
+
+    let mut pixels = Vec::with_capacity(len);
+    let mut index = [(0u8, 0u8, 0u8, 0u8); 64];
+    let mut pixel = (0u8, 0u8, 0u8, 255u8);
+    let mut data = bytes[14..].iter().copied();
+    let mut next = || data.next().ok_or_else(|| invalid("truncated QOI data"));
+    while pixels.len() < len {
+        let op = next()?;
+        let mut run = 1;
+        match op {
+            OP_RGB => pixel = (next()?, next()?, next()?, pixel.3),
+            OP_RGBA => pixel = (next()?, next()?, next()?, next()?),
+            _ => match op & MASK {
+                OP_INDEX => pixel = index[op as usize],
+                OP_DIFF => {
+                    let delta = |shift: u8| ((op >> shift) & 0x03).wrapping_sub(2);
+                    pixel.0 = pixel.0.wrapping_add(delta(4));
+                    pixel.1 = pixel.1.wrapping_add(delta(2));
+                    pixel.2 = pixel.2.wrapping_add(delta(0));
+                }
+                OP_LUMA => {
+                    let dg = (op & 0x3f).wrapping_sub(32);
+                    let byte = next()?;
+                    pixel.0 = pixel
+                        .0
+                        .wrapping_add(dg.wrapping_add(byte >> 4).wrapping_sub(8));
+                    pixel.1 = pixel.1.wrapping_add(dg);
+                    pixel.2 = pixel
+                        .2
+                        .wrapping_add(dg.wrapping_add(byte & 0x0f).wrapping_sub(8));
+                }
+                _ => run = (op & 0x3f) as usize + 1,
+            },
+        }
+        let (r, g, b, a) = pixel;
+        let slot = (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64;
+        index[slot] = pixel;
+        for _ in 0..run.min(len - pixels.len()) {
+            pixels.push((r, g, b));
+        }
+    }
+    Ok(Image::new(width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smooth gradients, a long run of one colour, repeats and jumps, so that every kind of
+    /// chunk is used
+    fn test_image() -> Image {
+        let (width, height) = (37, 23);
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                match y {
+                    0..4 => (10, 20, 30),
+                    4..8 => ((x * 2) as u8, (y * 3) as u8, (x + y) as u8),
+                    8..12 => [(255, 0, 0), (0, 255, 0), (0, 0, 255)][x % 3],
+                    _ => ((x * 97 + y * 13) as u8, (x * 31) as u8, (y * 71 + x) as u8),
+                }
+            })
+            .collect();
+        Image::new(width, height, pixels)
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Image> {
+        read(&mut &bytes[..])
+    }
+
+    #[test]
+    fn round_trip() {
+        let image = test_image();
+        let mut bytes = Vec::new();
+        write(&mut bytes, &image).unwrap();
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!((decoded.width, decoded.height), (image.width, image.height));
+        assert_eq!(decoded.pixels, image.pixels);
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        let image = Image::new(0, 0, Vec::new());
+        let mut bytes = Vec::new();
+        write(&mut bytes, &image).unwrap();
+        assert_eq!(decode(&bytes).unwrap().pixels, image.pixels);
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let mut bytes = Vec::new();
+        write(&mut bytes, &test_image()).unwrap();
+        bytes.truncate(bytes.len() / 2);
+        assert_eq!(
+            decode(&bytes).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn rejects_huge_dimensions() {
+        let mut bytes = b"qoif".to_vec();
+        bytes.extend(u32::MAX.to_be_bytes());
+        bytes.extend(u32::MAX.to_be_bytes());
+        bytes.extend([3, 0]);
+        bytes.extend(END_MARKER);
+        let err = decode(&bytes).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,49 @@
+//! Uncompressed 24-bit Truevision TGA encoder.
+//! See <https://www.dca.fee.unicamp.br/~martino/disciplinas/ea978/tgaffs.pdf>
+
+use std::io::{self, Write};
+
+use crate::image::Image;
+
+use super::Encoder;
+
+pub struct Tga;
+
+impl Encoder for Tga {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["tga"]
+    }
+
+    fn encode(&self, mut w: &mut dyn Write, image: &Image) -> io::Result<()> {
+        write(&mut w, image)
+    }
+}
+
+pub fn write(w: &mut impl Write, image: &Image) -> io::Result<()> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "image is too large for TGA");
+    let width = u16::try_from(image.width).map_err(|_| too_large())?;
+    let height = u16::try_from(image.height).map_err(|_| too_large())?;
+
+    w.write_all(&[
+        0, // image ID length
+        0, // colour map type: none
+        2, // image type: uncompressed truecolour
+        0, 0, 0, 0, 0, // colour map specification
+    ])?;
+    w.write_all(&0u16.to_le_bytes())?; // x origin
+    w.write_all(&0u16.to_le_bytes())?; // y origin
+    w.write_all(&width.to_le_bytes())?;
+    w.write_all(&height.to_le_bytes())?;
+    w.write_all(&[
+        24,   // bits per pixel
+        0x20, // image descriptor: rows are stored from the top down
+    ])?;
+
+    let bytes = image
+        .pixels
+        .iter()
+        .flat_map(|&(r, g, b)| [b, g, r])
+        .collect::<Vec<_>>();
+    w.write_all(&bytes)?;
+    w.flush()
+}
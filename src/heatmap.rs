@@ -0,0 +1,338 @@
+//! False-colour maps of the intermediate functions of a formula, e.g. `U(60, x, y)`,
+//! evaluated at every pixel of a canvas, and of the time each pixel took to render
+
+use std::fmt;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    image::Image,
+    overlay::{self, Frame, TEXT_COLOR},
+    softbuffer_color, xy_from_index, Art, Canvas,
+};
+
+/// Colours of [`ramp`] at evenly spaced points from the lowest value to the highest,
+/// sampled from matplotlib's viridis, which stays legible in grayscale and to colour-blind eyes
+const RAMP: [(u8, u8, u8); 10] = [
+    (68, 1, 84),
+    (72, 40, 120),
+    (62, 73, 137),
+    (49, 104, 142),
+    (38, 130, 142),
+    (31, 158, 137),
+    (53, 183, 121),
+    (110, 206, 88),
+    (181, 222, 43),
+    (253, 231, 37),
+];
+
+/// Colour of values that are NaN or infinite
+const NON_FINITE: (u8, u8, u8) = (128, 128, 128);
+
+/// Width of the legend's colour bar, in font pixels
+const BAR_WIDTH: usize = 120;
+
+/// Distance of the legend from the corner of the image, in font pixels
+const MARGIN: isize = 4;
+
+/// Shortest time on the log scale of [`times_to_image`], in seconds,
+/// below the resolution of the clock
+const MIN_SECONDS: f64 = 1e-9;
+
+/// Fraction of the times left out of each end of the scale by [`time_range`], as a few pixels
+/// are slowed down by whatever else the machine is doing
+const TIME_OUTLIERS: f64 = 0.01;
+
+/// An intermediate function of an artwork's formula, taking any number of `usize` arguments
+/// followed by `x` and `y`, e.g. `W(v, s, x, y)`. See [`Art::FUNCTIONS`].
+pub struct Function {
+    pub name: &'static str,
+    /// Names of the `usize` arguments, e.g. `["v", "s"]`
+    pub args: &'static [&'static str],
+    /// Calls the function with the `usize` arguments, in order, then `x` and `y`
+    pub eval: fn(&[usize], f64, f64) -> f64,
+}
+
+/// Lists the functions of a formula for [`Art::FUNCTIONS`], each with the names of its `usize`
+/// arguments, e.g. `functions![H(v), W(v, s), E()]` for `H(v, x, y)`, `W(v, s, x, y)`
+/// and `E(x, y)`
+#[macro_export]
+macro_rules! functions {
+    ( $( $name:ident ( $($arg:ident),* ) ),* $(,)? ) => {
+        &[$(
+            $crate::heatmap::Function {
+                name: stringify!($name),
+                args: &[$(stringify!($arg)),*],
+                eval: |args, x, y| {
+                    // unused by functions of just `x` and `y`
+                    #[allow(unused_mut, unused_variables)]
+                    let mut args = args.iter().copied();
+                    $( let $arg = args.next().expect("should have an argument for each name"); )*
+                    $name($($arg,)* x, y)
+                },
+            }
+        ),*]
+    };
+}
+
+impl fmt::Display for Function {
+    /// e.g. `W(v, s, x, y)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{name}(", name = self.name)?;
+        for arg in self.args {
+            write!(f, "{arg}, ")?;
+        }
+        write!(f, "x, y)")
+    }
+}
+
+/// A [`Function`] with its `usize` arguments, leaving `x` and `y` to vary over the canvas
+pub struct FunctionCall {
+    pub function: &'static Function,
+    pub args: Vec<usize>,
+}
+
+impl FunctionCall {
+    /// Looks up a call such as `U(60)`, `U(60, x, y)`, `E` or `E(x, y)` among the functions
+    /// of `Artwork`
+    pub fn parse<Artwork: Art>(call: &str) -> Result<Self, String> {
+        let call = call.trim();
+        let (name, args) = match call.split_once('(') {
+            Some((name, args)) => {
+                let args = args
+                    .strip_suffix(')')
+                    .ok_or_else(|| format!("expected a closing parenthesis in {call}"))?;
+                (name.trim(), args)
+            }
+            None => (call, ""),
+        };
+        let function = Artwork::FUNCTIONS
+            .iter()
+            .find(|function| function.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown function: {name}\navailable functions: {functions}",
+                    functions = Artwork::FUNCTIONS
+                        .iter()
+                        .map(Function::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+
+        let mut args = args
+            .split(',')
+            .map(str::trim)
+            .filter(|arg| !arg.is_empty())
+            .collect::<Vec<_>>();
+        if args.ends_with(&["x", "y"]) {
+            args.truncate(args.len() - 2);
+        }
+        if args.len() != function.args.len() {
+            return Err(format!(
+                "{name} takes {count} arguments before x and y: {function}",
+                count = function.args.len()
+            ));
+        }
+        let args = args
+            .iter()
+            .map(|arg| {
+                arg.parse()
+                    .map_err(|_| format!("expected a whole number, found: {arg}"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { function, args })
+    }
+
+    pub fn eval(&self, x: f64, y: f64) -> f64 {
+        (self.function.eval)(&self.args, x, y)
+    }
+}
+
+impl fmt::Display for FunctionCall {
+    /// e.g. `W(0, 20, x, y)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{name}(", name = self.function.name)?;
+        for arg in &self.args {
+            write!(f, "{arg}, ")?;
+        }
+        write!(f, "x, y)")
+    }
+}
+
+/// Value of `call` at the centre of every pixel of `canvas`
+pub fn evaluate<Artwork: Art>(call: &FunctionCall, canvas: Canvas) -> Image<f64> {
+    let values = (0..canvas.len())
+        .into_par_iter()
+        .map(|index| {
+            let (px, py) = xy_from_index(canvas.width, index);
+            let (m, n) = canvas.mn::<Artwork>(px as f64 + 0.5, py as f64 + 0.5);
+            let (x, y) = Artwork::xy(m, n);
+            call.eval(x, y)
+        })
+        .collect();
+    Image::new(canvas.width, canvas.height, values)
+}
+
+/// Lowest and highest finite values, or `None` if there are none
+pub fn range(values: &[f64]) -> Option<(f64, f64)> {
+    values
+        .iter()
+        .filter(|value| value.is_finite())
+        .fold(None, |range, &value| match range {
+            None => Some((value, value)),
+            Some((min, max)) => Some((value.min(min), value.max(max))),
+        })
+}
+
+/// Colour of `t` in `0..=1` on the ramp from the lowest value to the highest
+pub fn ramp(t: f64) -> (u8, u8, u8) {
+    let position = t.clamp(0., 1.) * (RAMP.len() - 1) as f64;
+    let i = (position as usize).min(RAMP.len() - 2);
+    let t = position - i as f64;
+    let ((r0, g0, b0), (r1, g1, b1)) = (RAMP[i], RAMP[i + 1]);
+    let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    (lerp(r0, r1), lerp(g0, g1), lerp(b0, b1))
+}
+
+/// Colour of `value` on the ramp from `min` to `max`
+pub fn colour(value: f64, (min, max): (f64, f64)) -> (u8, u8, u8) {
+    if !value.is_finite() {
+        NON_FINITE
+    } else if max > min {
+        ramp((value - min) / (max - min))
+    } else {
+        ramp(0.5)
+    }
+}
+
+/// Maps `values` onto the colour ramp from their lowest finite value to their highest,
+/// with a legend labelled `label` in the bottom-left corner
+pub fn to_image(values: &Image<f64>, label: &str) -> Image {
+    let range = range(&values.pixels).unwrap_or((0., 0.));
+    to_image_with(values, range, label, format_value)
+}
+
+/// Maps the seconds each pixel took to render onto the colour ramp on a log scale,
+/// as the cost of a formula can vary by orders of magnitude across the canvas
+pub fn times_to_image(times: &Image<f64>) -> Image {
+    let values = times
+        .pixels
+        .iter()
+        .map(|&seconds| log_seconds(seconds))
+        .collect();
+    let values = Image::new(times.width, times.height, values);
+    let range = time_range(&values.pixels).unwrap_or((0., 0.));
+    let label = "time per pixel, log scale";
+    to_image_with(&values, range, label, |value| {
+        format_seconds(10f64.powf(value))
+    })
+}
+
+/// Position of `seconds` on the log scale of [`times_to_image`]
+pub fn log_seconds(seconds: f64) -> f64 {
+    seconds.max(MIN_SECONDS).log10()
+}
+
+/// Range of the colour ramp for the times on the log scale of [`times_to_image`], from the
+/// 1st percentile to the 99th, or `None` if there are none
+pub fn time_range(log_seconds: &[f64]) -> Option<(f64, f64)> {
+    let mut values = log_seconds
+        .iter()
+        .copied()
+        .filter(|value| value.is_finite())
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return None;
+    }
+    // selecting rather than sorting, as the viewer redraws this while pixels arrive
+    let mut at = |fraction: f64| {
+        let index = ((values.len() - 1) as f64 * fraction).round() as usize;
+        *values.select_nth_unstable_by(index, f64::total_cmp).1
+    };
+    Some((at(TIME_OUTLIERS), at(1. - TIME_OUTLIERS)))
+}
+
+/// e.g. `12.3 us`, `4.56 ms` or `1.20 s`
+pub fn format_seconds(seconds: f64) -> String {
+    if seconds < 1e-3 {
+        format!("{:.1} us", seconds * 1e6)
+    } else if seconds < 1. {
+        format!("{:.2} ms", seconds * 1e3)
+    } else {
+        format!("{seconds:.2} s")
+    }
+}
+
+/// Maps `values` onto the colour ramp across `range`, with the ends of the legend written by
+/// `format`
+fn to_image_with(
+    values: &Image<f64>,
+    range: (f64, f64),
+    label: &str,
+    format: impl Fn(f64) -> String,
+) -> Image {
+    let mut pixels = values
+        .pixels
+        .iter()
+        .map(|&value| softbuffer_color(colour(value, range)))
+        .collect::<Vec<_>>();
+
+    let non_finite = values.pixels.iter().filter(|v| !v.is_finite()).count();
+    let mut frame = Frame::new(&mut pixels, values.width, values.height);
+    let (min, max) = (format(range.0), format(range.1));
+    legend(&mut frame, label, (&min, &max), non_finite);
+    Image::from_softbuffer(values.width, values.height, &pixels)
+}
+
+/// Draws the colour bar from `min` to `max`, labelled, on a panel in the bottom-left corner
+fn legend(frame: &mut Frame, label: &str, (min, max): (&str, &str), non_finite: usize) {
+    // about the size of the viewer's text on a native canvas
+    let scale = (frame.width / 600).max(1);
+    let mut lines = vec![label.to_string(), String::new(), String::new()];
+    if non_finite > 0 {
+        lines.push(format!("{non_finite} pixels NaN or infinite"));
+    }
+
+    let padding = overlay::padding(scale);
+    let line_height = overlay::line_height(scale);
+    let (text_width, height) = overlay::panel_size(&lines, scale);
+    let bar_width = (BAR_WIDTH * scale)
+        .max(text_width - 2 * padding)
+        .max(overlay::text_size(&format!("{min}  {max}"), scale).0);
+    let width = bar_width + 2 * padding;
+    let margin = MARGIN * scale as isize;
+    let left = margin;
+    let top = frame.height as isize - margin - height as isize;
+    frame.shade(left, top, width, height);
+
+    let text_left = left + padding as isize;
+    let line_top = |line: usize| top + (padding + line * line_height) as isize;
+    for (i, line) in lines.iter().enumerate() {
+        frame.text(text_left, line_top(i), line, scale, TEXT_COLOR);
+    }
+    for column in 0..bar_width {
+        let color = softbuffer_color(ramp(column as f64 / (bar_width - 1).max(1) as f64));
+        let bar_height = line_height - scale;
+        frame.fill(
+            text_left + column as isize,
+            line_top(1),
+            1,
+            bar_height,
+            color,
+        );
+    }
+    frame.text(text_left, line_top(2), min, scale, TEXT_COLOR);
+    let max_width = overlay::text_size(max, scale).0;
+    let max_left = text_left + (bar_width - max_width) as isize;
+    frame.text(max_left, line_top(2), max, scale, TEXT_COLOR);
+}
+
+/// Fixed-point for everyday magnitudes, scientific notation otherwise
+fn format_value(value: f64) -> String {
+    if value == 0. || (1e-3..1e5).contains(&value.abs()) {
+        format!("{value:.4}")
+    } else {
+        format!("{value:.3e}")
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    fs::File,
+    io,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use crate::{
+    canvas::Rect,
+    encode::{self, pfm, ppm, qoi},
+    render::UNRENDERED,
+    utils::rgb_from_softbuffer_color,
+};
+
+/// An owned image, stored row by row from the top left.
+/// Pixels are 8-bit RGB by default, or the unquantised `f64` channel values of
+/// [`Art::channels_xy`](crate::Art::channels_xy) for [`FloatImage`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image<Pixel = (u8, u8, u8)> {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Pixel>,
+}
+
+/// Channel values before quantisation, nominally in `0..=255`
+pub type FloatImage = Image<(f64, f64, f64)>;
+
+impl<Pixel: Copy> Image<Pixel> {
+    pub fn new(width: usize, height: usize, pixels: Vec<Pixel>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixel count should match the image dimensions"
+        );
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Copies `pixels`, laid out row by row, into `rect`
+    pub fn paste(&mut self, rect: Rect, pixels: &[Pixel]) {
+        assert_eq!(
+            pixels.len(),
+            rect.len(),
+            "pixel count should match the rect"
+        );
+        for (row, src) in pixels.chunks(rect.width.max(1)).enumerate() {
+            let start = (rect.y + row) * self.width + rect.x;
+            self.pixels[start..start + rect.width].copy_from_slice(src);
+        }
+    }
+}
+
+impl Image {
+    /// Colour that marks [`UNRENDERED`] pixels in [`Image::from_softbuffer`]
+    pub const UNRENDERED_MARKER: (u8, u8, u8) = (255, 0, 255);
+
+    /// Converts pixels in the softbuffer format, e.g. the viewer's partially rendered canvas,
+    /// marking any that are [`UNRENDERED`] with [`Image::UNRENDERED_MARKER`]
+    pub fn from_softbuffer(width: usize, height: usize, pixels: &[u32]) -> Self {
+        let pixels = pixels
+            .iter()
+            .map(|&color| match color {
+                UNRENDERED => Self::UNRENDERED_MARKER,
+                color => rgb_from_softbuffer_color(color),
+            })
+            .collect();
+        Self::new(width, height, pixels)
+    }
+
+    /// Writes the image to `path`, in the format given by its extension, see [`encode::ENCODERS`]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let encoder = encode::for_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unsupported file extension, expected one of: {extensions}",
+                    extensions = encode::extensions().collect::<Vec<_>>().join(", ")
+                ),
+            )
+        })?;
+        let file = File::create(path)?;
+        encoder.encode(&mut BufWriter::new(file), self)
+    }
+
+    /// Reads the image at `path`, in the format given by its extension, see [`encode::READABLE`]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let read = match extension.as_deref() {
+            Some("ppm") => ppm::read,
+            Some("qoi") => qoi::read,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "unsupported file extension, expected one of: {extensions}",
+                        extensions = encode::READABLE.join(", ")
+                    ),
+                ))
+            }
+        };
+        read(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+impl FloatImage {
+    /// Writes the image to `path` as a Portable Float Map
+    pub fn save_pfm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = File::create(path)?;
+        pfm::write(&mut BufWriter::new(file), self)
+    }
+}
@@ -0,0 +1,159 @@
+//! Renders running in the background, which can be paused, resumed, cancelled and restarted
+//! through a [`RenderJob`]
+
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex,
+    },
+    time::Instant,
+};
+
+use crate::{
+    render::{render_controlled, PixelReady, RenderOptions, Viewport},
+    Art, Rect,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    /// Waiting to be resumed, having finished the batches it had started
+    Paused,
+    /// Stopped for good, by [`RenderJob::cancel`] or because its receiver was dropped
+    Cancelled,
+    /// Every batch has been sent
+    Finished,
+}
+
+/// The [`JobState`] of a render, shared between its [`RenderJob`] and the threads rendering it
+#[derive(Debug)]
+pub(crate) struct Control {
+    state: Mutex<JobState>,
+    changed: Condvar,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(JobState::Running),
+            changed: Condvar::new(),
+        }
+    }
+}
+
+impl Control {
+    pub(crate) fn state(&self) -> JobState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Moves to `to` if the job is in one of `from`
+    fn transition(&self, from: &[JobState], to: JobState) {
+        let mut state = self.state.lock().unwrap();
+        if from.contains(&state) {
+            *state = to;
+            self.changed.notify_all();
+        }
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.state() == JobState::Running
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.transition(&[JobState::Running, JobState::Paused], JobState::Cancelled);
+    }
+
+    pub(crate) fn finish(&self) {
+        self.transition(&[JobState::Running], JobState::Finished);
+    }
+
+    /// Blocks while the job is paused, returning whether it should carry on
+    pub(crate) fn wait_while_paused(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        let state = self
+            .changed
+            .wait_while(state, |state| *state == JobState::Paused)
+            .unwrap();
+        *state == JobState::Running
+    }
+
+    /// Blocks until the job stops running or `deadline` passes, returning whether it is still
+    /// running
+    pub(crate) fn wait_until(&self, deadline: Instant) -> bool {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let state = self.state.lock().unwrap();
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, timeout, |state| *state == JobState::Running)
+            .unwrap();
+        *state == JobState::Running
+    }
+}
+
+/// A render of part of a canvas on its own thread, sending batches of pixels as they are ready,
+/// like [`render_region_batches_prioritised`](crate::render_region_batches_prioritised).
+/// Dropping the job cancels it.
+pub struct RenderJob {
+    /// [`render_controlled`] for the artwork, so [`RenderJob::restart`] needs no type parameter
+    render: fn(Rect, RenderOptions, &Viewport, &Control, Sender<PixelReady>),
+    viewport: Viewport,
+    control: Arc<Control>,
+}
+
+impl RenderJob {
+    /// Starts rendering `rect` of `options.canvas`, nearest to `viewport` first,
+    /// returning the job and the receiver of its batches, which disconnects once the job stops
+    pub fn start<Artwork: Art>(
+        rect: Rect,
+        options: RenderOptions,
+        viewport: Viewport,
+    ) -> (Self, Receiver<PixelReady>) {
+        let mut job = Self {
+            render: render_controlled::<Artwork>,
+            viewport,
+            control: Default::default(),
+        };
+        let rx = job.restart(rect, options);
+        (job, rx)
+    }
+
+    /// Cancels the render and starts rendering `rect` of `options.canvas` in its place,
+    /// returning the receiver of the new batches. Batches of the old render still on their way
+    /// go to the old receiver, so they cannot be mistaken for new ones.
+    pub fn restart(&mut self, rect: Rect, options: RenderOptions) -> Receiver<PixelReady> {
+        self.control.cancel();
+        self.control = Default::default();
+        let (tx, rx) = mpsc::channel();
+        let (render, viewport, control) =
+            (self.render, self.viewport.clone(), self.control.clone());
+        // not on the rayon thread pool, which pausing would otherwise hold up
+        std::thread::spawn(move || render(rect, options, &viewport, &control, tx));
+        rx
+    }
+
+    /// Stops taking new batches until [`RenderJob::resume`], leaving the rayon threads free
+    pub fn pause(&self) {
+        self.control
+            .transition(&[JobState::Running], JobState::Paused);
+    }
+
+    pub fn resume(&self) {
+        self.control
+            .transition(&[JobState::Paused], JobState::Running);
+    }
+
+    /// Stops the render for good. Batches already underway are still sent.
+    pub fn cancel(&self) {
+        self.control.cancel();
+    }
+
+    pub fn state(&self) -> JobState {
+        self.control.state()
+    }
+}
+
+impl Drop for RenderJob {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
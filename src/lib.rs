@@ -5,14 +5,86 @@
 
 pub mod art;
 
+pub mod canvas;
+pub use canvas::{Canvas, Rect};
+
+pub mod checkpoint;
+
+pub mod diff;
+pub use diff::Diff;
+
+pub mod encode;
+
+pub mod heatmap;
+pub use heatmap::Function;
+
+pub mod image;
+pub use image::{FloatImage, Image};
+
+pub mod job;
+pub use job::{JobState, RenderJob};
+
+pub mod order;
+pub use order::Order;
+
+pub mod overlay;
+
+pub mod progress;
+pub use progress::Progress;
+
+pub mod registry;
+
+pub mod render;
+pub use render::*;
+
 pub mod run;
 pub use run::*;
 
+pub mod sampling;
+pub use sampling::Sampling;
+
+pub mod trace;
+pub use trace::*;
+
 pub mod utils;
 pub use utils::*;
 
 pub trait Art {
+    /// Name used to select the artwork on the command line, e.g. `sunflower_field`
+    const NAME: &'static str;
+    /// Title of the original artwork, e.g. `Sunflower Field`
+    const TITLE: &'static str;
+    /// Native canvas width, i.e. `m` runs from `1` to `FULL_M`
     const FULL_M: usize;
+    /// Native canvas height, i.e. `n` runs from `1` to `FULL_N`
     const FULL_N: usize;
-    fn draw(m: f64, n: f64) -> (u8, u8, u8);
+    /// Intermediate functions of the formula that can be drawn as a heatmap, see [`heatmap`]
+    const FUNCTIONS: &'static [Function] = &[];
+
+    /// Colour at the formula's own coordinates `(x, y)`
+    fn draw_xy(x: f64, y: f64) -> (u8, u8, u8);
+
+    /// Red, green and blue at the formula's own coordinates `(x, y)`,
+    /// before they are rounded and saturated by `rgb`
+    fn channels_xy(x: f64, y: f64) -> (f64, f64, f64);
+
+    /// Maps the native pixel coordinates `(m, n)` to the formula's `(x, y)`,
+    /// with the origin in the middle of the canvas and `y` pointing up
+    fn xy(m: f64, n: f64) -> (f64, f64) {
+        let half_m = Self::FULL_M as f64 / 2.;
+        let half_n = Self::FULL_N as f64 / 2.;
+        ((m - half_m) / half_n, (half_n + 1. - n) / half_n)
+    }
+
+    /// Colour at the native pixel coordinates `(m, n)`, which need not be whole numbers
+    fn draw(m: f64, n: f64) -> (u8, u8, u8) {
+        let (x, y) = Self::xy(m, n);
+        Self::draw_xy(x, y)
+    }
+
+    /// Channel values at the native pixel coordinates `(m, n)`, see [`Art::channels_xy`]
+    fn channels(m: f64, n: f64) -> (f64, f64, f64) {
+        let (x, y) = Self::xy(m, n);
+        Self::channels_xy(x, y)
+    }
 }
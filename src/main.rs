@@ -1,5 +1,682 @@
-use naderi_yeganeh_art::*;
+use std::{
+    collections::HashMap,
+    env, fmt, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-fn main() {
-    run::<art::strawberries::Artwork>();
+use naderi_yeganeh_art::{registry::Visitor, *};
+
+const USAGE: &str = "\
+usage: naderi_yeganeh_art <command> [args]
+
+commands:
+  list                                 list the available artworks
+  view <artwork>...                    render artworks in a window, switching between them with Tab
+  render <artwork> <output>            render an artwork to an image file, in the format given by
+                                       the extension of <output>: png, qoi, bmp, ppm or tga, or pfm
+                                       for the channel values before quantisation
+  trace <artwork> <m> <n>              print the calls that produce the colour of pixel (m, n)
+  heatmap <artwork> <function> <output>
+                                       draw an intermediate function of the formula over the
+                                       canvas in false colour with a legend, e.g. U(60) for
+                                       U(60, x, y), or list the functions with `heatmap <artwork>`
+  diff <a> <b> <output>                compare two renders pixel by pixel, each an artwork or a
+                                       saved ppm or qoi render, saving their difference amplified
+                                       and listing the pixels that differ. Artworks are rendered
+                                       at the size of a saved render unless a size is given
+
+view, render, heatmap and diff options:
+  --width <pixels>                     output width, keeping the aspect ratio unless --height is given
+  --height <pixels>                    output height, keeping the aspect ratio unless --width is given
+  --scale <factor>                     output size relative to the artwork's native size
+
+view, render and diff options:
+  --sampling <pattern>                 samples per pixel: single (default), grid:<n> or jittered:<n>,
+                                       taking n x n samples that are averaged in linear light,
+                                       with n at most 64
+  --order <order>                      order to render pixels in: linear (default) or progressive,
+                                       which shows a coarse preview of the whole canvas first
+
+view options:
+  --reference <render>                 an artwork or a saved ppm or qoi render to compare with,
+                                       showing the differences with d
+
+diff options:
+  --amplify <factor>                   multiply the differences by <factor> (default: enough to
+                                       bring the largest to full brightness)
+
+render options:
+  --region <x,y,width,height>          render only this rectangle of the canvas
+  --checkpoint <dir>                   render in tiles, saving progress to <dir> and resuming from it
+  --tile-size <pixels>                 size of the square tiles used with --checkpoint (default 64)
+  --timeout <seconds>                  give up on the render after this long, without saving, though
+                                       with --checkpoint the tiles rendered so far are kept
+  --times <output>                     also save how long each pixel took to render, as a heatmap
+                                       on a log scale, to see where the formula is expensive";
+
+fn main() -> ExitCode {
+    match Args::parse(env::args().skip(1)).and_then(|args| args.run()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Positional arguments plus any `--key value` options
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            if let Some(key) = arg.strip_prefix("--") {
+                let value = args
+                    .next_if(|value| !value.starts_with("--"))
+                    .unwrap_or_default();
+                options.insert(key.to_string(), value);
+            } else {
+                positional.push(arg);
+            }
+        }
+        Ok(Self {
+            positional,
+            options,
+        })
+    }
+
+    /// Removes the option `--key` and parses its value
+    fn take<T: FromStr>(&mut self, key: &str) -> Result<Option<T>, String>
+    where
+        T::Err: fmt::Display,
+    {
+        self.options
+            .remove(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|error| format!("invalid value for --{key}: {value:?}, {error}"))
+            })
+            .transpose()
+    }
+
+    fn options(&mut self) -> Result<Options, String> {
+        Ok(Options {
+            sampling: self.take("sampling")?.unwrap_or_default(),
+            order: self.take("order")?.unwrap_or_default(),
+            ..self.size()?
+        })
+    }
+
+    /// Just the output size, leaving the other options at their defaults
+    fn size(&mut self) -> Result<Options, String> {
+        Ok(Options {
+            width: self.take("width")?,
+            height: self.take("height")?,
+            scale: self.take("scale")?,
+            sampling: Sampling::default(),
+            order: Order::default(),
+        })
+    }
+
+    fn run(mut self) -> Result<(), String> {
+        let positional = std::mem::take(&mut self.positional);
+        let positional = positional.iter().map(String::as_str).collect::<Vec<_>>();
+        let command = match positional.as_slice() {
+            ["list"] => Command::List,
+            ["view", names @ ..] if !names.is_empty() => Command::View {
+                names,
+                options: self.options()?,
+                reference: self.take("reference")?,
+            },
+            ["render", name, path] => Command::Render {
+                name,
+                render: Render {
+                    path: PathBuf::from(path),
+                    options: self.options()?,
+                    region: self.take("region")?,
+                    checkpoint: self.take("checkpoint")?,
+                    tile_size: self.take("tile-size")?.unwrap_or(64),
+                    timeout: self.take("timeout")?,
+                    times: self.take("times")?,
+                },
+            },
+            ["trace", name, m, n] => Command::Trace {
+                name,
+                trace: Trace {
+                    m: parse_number(m)?,
+                    n: parse_number(n)?,
+                },
+            },
+            ["heatmap", name] => Command::Functions { name },
+            ["heatmap", name, function, path] => Command::Heatmap {
+                name,
+                heatmap: Heatmap {
+                    function: function.to_string(),
+                    path: PathBuf::from(path),
+                    options: self.size()?,
+                },
+            },
+            ["diff", a, b, path] => Command::Diff {
+                compare: Compare {
+                    a,
+                    b,
+                    path: PathBuf::from(path),
+                    options: self.options()?,
+                    amplify: self.take("amplify")?,
+                },
+            },
+            _ => return Err(USAGE.to_string()),
+        };
+        if let Some(key) = self.options.keys().next() {
+            return Err(format!("unknown option: --{key}\n\n{USAGE}"));
+        }
+
+        match command {
+            Command::List => {
+                for name in registry::NAMES {
+                    visit(name, List)?;
+                }
+                Ok(())
+            }
+            Command::View {
+                names,
+                mut options,
+                reference,
+            } => {
+                let reference = match reference {
+                    Some(source) => Some(Arc::new(load_render(&source, &mut options)?)),
+                    None => None,
+                };
+                let view = View { options, reference };
+                let artworks = names
+                    .iter()
+                    .map(|name| visit(name, &view)?)
+                    .collect::<Result<_, _>>()?;
+                run_many(artworks);
+                Ok(())
+            }
+            Command::Render { name, render } => visit(name, render)?,
+            Command::Trace { name, trace } => visit(name, trace),
+            Command::Functions { name } => visit(name, Functions),
+            Command::Heatmap { name, heatmap } => visit(name, heatmap)?,
+            Command::Diff { compare } => compare.run(),
+        }
+    }
+}
+
+enum Command<'a> {
+    List,
+    View {
+        names: &'a [&'a str],
+        options: Options,
+        /// An artwork or a saved render
+        reference: Option<String>,
+    },
+    Render {
+        name: &'a str,
+        render: Render,
+    },
+    Trace {
+        name: &'a str,
+        trace: Trace,
+    },
+    Functions {
+        name: &'a str,
+    },
+    Heatmap {
+        name: &'a str,
+        heatmap: Heatmap,
+    },
+    Diff {
+        compare: Compare<'a>,
+    },
+}
+
+/// Render options, with the output size resolved against the artwork's native size
+struct Options {
+    width: Option<usize>,
+    height: Option<usize>,
+    scale: Option<f64>,
+    sampling: Sampling,
+    order: Order,
+}
+
+impl Options {
+    /// Renders at `width` x `height` unless a size was given, e.g. to match a saved render
+    fn default_size(&mut self, width: usize, height: usize) {
+        if (self.width, self.height, self.scale) == (None, None, None) {
+            self.width = Some(width);
+            self.height = Some(height);
+        }
+    }
+
+    fn resolve<Artwork: Art>(&self) -> Result<RenderOptions, String> {
+        Ok(RenderOptions {
+            canvas: self.canvas::<Artwork>()?,
+            sampling: self.sampling,
+            order: self.order,
+            timed: false,
+        })
+    }
+
+    fn canvas<Artwork: Art>(&self) -> Result<Canvas, String> {
+        if self.width == Some(0) || self.height == Some(0) {
+            return Err("--width and --height should be greater than 0".to_string());
+        }
+        match (self.width, self.height, self.scale) {
+            (None, None, None) => Ok(Canvas::native::<Artwork>()),
+            (None, None, Some(scale)) if scale > 0. && scale.is_finite() => {
+                Ok(Canvas::scaled::<Artwork>(scale))
+            }
+            (None, None, Some(_)) => Err("--scale should be greater than 0".to_string()),
+            (Some(width), None, None) => Ok(Canvas::with_width::<Artwork>(width)),
+            (None, Some(height), None) => Ok(Canvas::with_height::<Artwork>(height)),
+            (Some(width), Some(height), None) => Ok(Canvas::new(width, height)),
+            (_, _, Some(_)) => {
+                Err("--scale cannot be combined with --width or --height".to_string())
+            }
+        }
+    }
+}
+
+fn visit<V: Visitor>(name: &str, visitor: V) -> Result<V::Output, String> {
+    registry::visit(name, visitor).ok_or_else(|| {
+        format!(
+            "unknown artwork: {name}\navailable artworks: {names}",
+            names = registry::NAMES.join(", ")
+        )
+    })
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected a number, found: {value}"))
+}
+
+struct List;
+
+impl Visitor for List {
+    type Output = ();
+
+    fn visit<Artwork: Art>(self) {
+        println!(
+            "{name:<20}{title} ({m}x{n})",
+            name = Artwork::NAME,
+            title = Artwork::TITLE,
+            m = Artwork::FULL_M,
+            n = Artwork::FULL_N,
+        );
+    }
+}
+
+struct View {
+    options: Options,
+    reference: Option<Arc<Image>>,
+}
+
+impl Visitor for &View {
+    type Output = Result<ViewedArtwork, String>;
+
+    fn visit<Artwork: Art>(self) -> Self::Output {
+        let options = self.options.resolve::<Artwork>()?;
+        let artwork = ViewedArtwork::new::<Artwork>(options);
+        let Some(reference) = &self.reference else {
+            return Ok(artwork);
+        };
+        let canvas = options.canvas;
+        if (reference.width, reference.height) != (canvas.width, canvas.height) {
+            return Err(format!(
+                "the reference is {width}x{height}, but {name} is rendered at {canvas_width}x{canvas_height}",
+                width = reference.width,
+                height = reference.height,
+                name = Artwork::NAME,
+                canvas_width = canvas.width,
+                canvas_height = canvas.height
+            ));
+        }
+        Ok(artwork.with_reference(reference.clone()))
+    }
+}
+
+struct Render {
+    path: PathBuf,
+    options: Options,
+    region: Option<Rect>,
+    checkpoint: Option<PathBuf>,
+    tile_size: usize,
+    /// Seconds
+    timeout: Option<f64>,
+    /// Where to save the time each pixel took to render
+    times: Option<PathBuf>,
+}
+
+impl Visitor for Render {
+    type Output = Result<(), String>;
+
+    fn visit<Artwork: Art>(self) -> Self::Output {
+        let Self {
+            path,
+            options,
+            region,
+            checkpoint,
+            tile_size,
+            timeout,
+            times,
+        } = self;
+        let options = options.resolve::<Artwork>()?;
+        if tile_size == 0 {
+            return Err("--tile-size should be greater than 0".to_string());
+        }
+        let timeout = timeout
+            .map(|timeout| {
+                Duration::try_from_secs_f64(timeout)
+                    .map_err(|_| "--timeout should be a number of seconds".to_string())
+            })
+            .transpose()?;
+        if let Some(rect) = region {
+            if checkpoint.is_some() {
+                return Err("--region cannot be combined with --checkpoint".to_string());
+            }
+            if rect.is_empty() || !options.canvas.rect().contains(&rect) {
+                return Err(format!(
+                    "--region should be inside the {width}x{height} canvas",
+                    width = options.canvas.width,
+                    height = options.canvas.height
+                ));
+            }
+        }
+        let is_pfm = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pfm"));
+        if !is_pfm && encode::for_path(&path).is_none() {
+            return Err(format!(
+                "unsupported output file {path}, expected one of: {extensions}, pfm",
+                path = path.display(),
+                extensions = encode::extensions().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if times.is_some() && (is_pfm || region.is_some() || checkpoint.is_some()) {
+            return Err(
+                "--times is not supported with --region, --checkpoint or PFM output".to_string(),
+            );
+        }
+        if let Some(times) = times
+            .as_ref()
+            .filter(|times| encode::for_path(times).is_none())
+        {
+            return Err(format!(
+                "unsupported --times file {path}, expected one of: {extensions}",
+                path = times.display(),
+                extensions = encode::extensions().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        let write_err =
+            |err: io::Error| format!("failed to write {path}: {err}", path = path.display());
+
+        let time_started = Instant::now();
+        let deadline = timeout.map(|timeout| time_started + timeout);
+        let gave_up = || {
+            format!(
+                "gave up after {timeout:?}",
+                timeout = time_started.elapsed()
+            )
+        };
+        if is_pfm {
+            if checkpoint.is_some() {
+                return Err("--checkpoint is not supported for PFM output".to_string());
+            }
+            let rect = region.unwrap_or(options.canvas.rect());
+            let pixels = render_region_channels_with_deadline::<Artwork>(rect, options, deadline)
+                .ok_or_else(gave_up)?;
+            println!("Finished in {elapsed:?}", elapsed = time_started.elapsed());
+            FloatImage::new(rect.width, rect.height, pixels)
+                .save_pfm(&path)
+                .map_err(write_err)?;
+        } else {
+            let (image, pixel_times) = match (region, checkpoint) {
+                (Some(rect), _) => {
+                    let pixels = render_region_with_deadline::<Artwork>(rect, options, deadline)
+                        .ok_or_else(gave_up)?;
+                    (Image::new(rect.width, rect.height, pixels), None)
+                }
+                (None, Some(dir)) => {
+                    let (image, resumed) = render_resumable_with_deadline::<Artwork>(
+                        options,
+                        tile_size,
+                        &dir,
+                        deadline,
+                        print_progress,
+                    )
+                    .map_err(|err| {
+                        format!("failed to checkpoint to {dir}: {err}", dir = dir.display())
+                    })?
+                    .ok_or_else(|| {
+                        format!(
+                            "{gave_up}, keeping the tiles rendered so far in {dir} to resume from",
+                            gave_up = gave_up(),
+                            dir = dir.display()
+                        )
+                    })?;
+                    if resumed > 0 {
+                        println!(
+                            "Resumed from {dir}: {resumed} of {total} tiles were already rendered",
+                            dir = dir.display(),
+                            total = options.canvas.tiles(tile_size).len()
+                        );
+                    }
+                    (image, None)
+                }
+                (None, None) => {
+                    if times.is_some() {
+                        let (image, times) =
+                            render_timed::<Artwork>(options, deadline, print_progress)
+                                .ok_or_else(gave_up)?;
+                        (image, Some(times))
+                    } else {
+                        let image =
+                            render_with_deadline::<Artwork>(options, deadline, print_progress)
+                                .ok_or_else(gave_up)?;
+                        (image, None)
+                    }
+                }
+            };
+            println!("Finished in {elapsed:?}", elapsed = time_started.elapsed());
+            image.save(&path).map_err(write_err)?;
+            if let (Some(pixel_times), Some(times)) = (pixel_times, times) {
+                heatmap::times_to_image(&pixel_times)
+                    .save(&times)
+                    .map_err(|err| {
+                        format!("failed to write {path}: {err}", path = times.display())
+                    })?;
+                println!("Saved the time per pixel to {path}", path = times.display());
+            }
+        }
+        println!("Saved to {path}", path = path.display());
+        Ok(())
+    }
+}
+
+fn print_progress(progress: &Progress) {
+    println!("{progress}");
+}
+
+struct Trace {
+    m: f64,
+    n: f64,
+}
+
+impl Visitor for Trace {
+    type Output = ();
+
+    fn visit<Artwork: Art>(self) {
+        trace::<Artwork>(self.m, self.n).print();
+    }
+}
+
+struct Functions;
+
+impl Visitor for Functions {
+    type Output = ();
+
+    fn visit<Artwork: Art>(self) {
+        for function in Artwork::FUNCTIONS {
+            println!("{function}");
+        }
+    }
+}
+
+struct Heatmap {
+    function: String,
+    path: PathBuf,
+    options: Options,
+}
+
+impl Visitor for Heatmap {
+    type Output = Result<(), String>;
+
+    fn visit<Artwork: Art>(self) -> Self::Output {
+        let call = heatmap::FunctionCall::parse::<Artwork>(&self.function)?;
+        let canvas = self.options.canvas::<Artwork>()?;
+        if encode::for_path(&self.path).is_none() {
+            return Err(format!(
+                "unsupported output file {path}, expected one of: {extensions}",
+                path = self.path.display(),
+                extensions = encode::extensions().collect::<Vec<_>>().join(", ")
+            ));
+        }
+
+        let time_started = Instant::now();
+        let values = heatmap::evaluate::<Artwork>(&call, canvas);
+        println!("Finished in {elapsed:?}", elapsed = time_started.elapsed());
+        if let Some((min, max)) = heatmap::range(&values.pixels) {
+            println!("{call} ranges from {min} to {max}");
+        }
+        heatmap::to_image(&values, &call.to_string())
+            .save(&self.path)
+            .map_err(|err| format!("failed to write {path}: {err}", path = self.path.display()))?;
+        println!("Saved to {path}", path = self.path.display());
+        Ok(())
+    }
+}
+
+/// Loads the saved render at `source`, or `None` if it names an artwork
+fn load_saved(source: &str) -> Result<Option<Image>, String> {
+    if registry::NAMES.contains(&source) {
+        return Ok(None);
+    }
+    if !Path::new(source).exists() {
+        return Err(format!(
+            "no artwork or saved render called {source}\navailable artworks: {names}",
+            names = registry::NAMES.join(", ")
+        ));
+    }
+    Image::load(source)
+        .map(Some)
+        .map_err(|err| format!("failed to read {source}: {err}"))
+}
+
+/// Loads the saved render at `source`, making it the default size of `options`,
+/// or renders the artwork it names
+fn load_render(source: &str, options: &mut Options) -> Result<Image, String> {
+    match load_saved(source)? {
+        Some(image) => {
+            options.default_size(image.width, image.height);
+            Ok(image)
+        }
+        None => visit(source, RenderImage { options })?,
+    }
+}
+
+/// Renders an artwork in memory, e.g. to compare it with another
+struct RenderImage<'a> {
+    options: &'a Options,
+}
+
+impl Visitor for RenderImage<'_> {
+    type Output = Result<Image, String>;
+
+    fn visit<Artwork: Art>(self) -> Self::Output {
+        let options = self.options.resolve::<Artwork>()?;
+        println!("Rendering {name}", name = Artwork::NAME);
+        Ok(render_with_progress::<Artwork>(options, print_progress))
+    }
+}
+
+struct Compare<'a> {
+    /// Each an artwork or a saved render
+    a: &'a str,
+    b: &'a str,
+    path: PathBuf,
+    options: Options,
+    amplify: Option<f64>,
+}
+
+impl Compare<'_> {
+    fn run(self) -> Result<(), String> {
+        let Self {
+            a,
+            b,
+            path,
+            mut options,
+            amplify,
+        } = self;
+        if amplify.is_some_and(|amplify| !(amplify > 0. && amplify.is_finite())) {
+            return Err("--amplify should be greater than 0".to_string());
+        }
+        if encode::for_path(&path).is_none() {
+            return Err(format!(
+                "unsupported output file {path}, expected one of: {extensions}",
+                path = path.display(),
+                extensions = encode::extensions().collect::<Vec<_>>().join(", ")
+            ));
+        }
+
+        // saved renders are loaded first, so the artworks can be rendered at their size
+        let (saved_a, saved_b) = (load_saved(a)?, load_saved(b)?);
+        if let Some(image) = saved_a.as_ref().or(saved_b.as_ref()) {
+            options.default_size(image.width, image.height);
+        }
+        let image_a = match saved_a {
+            Some(image) => image,
+            None => load_render(a, &mut options)?,
+        };
+        let image_b = match saved_b {
+            Some(image) => image,
+            None => load_render(b, &mut options)?,
+        };
+        if (image_a.width, image_a.height) != (image_b.width, image_b.height) {
+            return Err(format!(
+                "{a} is {width_a}x{height_a}, but {b} is {width_b}x{height_b}",
+                width_a = image_a.width,
+                height_a = image_a.height,
+                width_b = image_b.width,
+                height_b = image_b.height
+            ));
+        }
+
+        let diff = Diff::new(&image_a, &image_b);
+        diff.print(diff::LISTED_DIFFERENCES);
+        let gain = amplify.unwrap_or(diff.gain());
+        diff.to_image(gain)
+            .save(&path)
+            .map_err(|err| format!("failed to write {path}: {err}", path = path.display()))?;
+        println!("Saved to {path}", path = path.display());
+        if diff.differing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{a} and {b} differ"))
+        }
+    }
 }
@@ -0,0 +1,209 @@
+use std::{fmt, ops::Range, str::FromStr};
+
+use crate::utils::xy_from_index;
+
+/// Size of the blocks filled by the first pass of [`Order::Progressive`]
+pub const COARSEST_BLOCK: usize = 16;
+
+/// The order in which [`render_batches`](crate::render_batches) visits the pixels of a canvas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// Row by row
+    #[default]
+    Linear,
+    /// Coarse to fine, like Adam7 interlacing. The first pass renders every
+    /// [`COARSEST_BLOCK`]th pixel in each direction and fills the block below and to the right
+    /// of it, so a low resolution preview of the whole canvas appears quickly. Every later pass
+    /// halves the block size, rendering only the pixels no earlier pass has.
+    Progressive,
+}
+
+/// A square of pixels that all take the colour of the top-left one,
+/// relative to the rect being rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub x: usize,
+    pub y: usize,
+    pub size: usize,
+}
+
+impl Order {
+    /// Ranges of step indices, see [`Order::block`], for each pass over a `width` x `height`
+    /// rect. Every pixel is rendered by exactly one step.
+    pub fn passes(&self, width: usize, height: usize) -> Vec<Range<usize>> {
+        let mut start = 0;
+        self.block_sizes()
+            .enumerate()
+            .map(|(pass, size)| {
+                let len = pass_len(width, height, size, pass == 0);
+                start += len;
+                start - len..start
+            })
+            .collect()
+    }
+
+    /// The block rendered by the `step`th step over a `width` x `height` rect,
+    /// counting through the passes in order
+    pub fn block(&self, width: usize, height: usize, step: usize) -> Block {
+        let mut step = step;
+        for (pass, size) in self.block_sizes().enumerate() {
+            let len = pass_len(width, height, size, pass == 0);
+            if step < len {
+                return pass_block(width, size, pass == 0, step);
+            }
+            step -= len;
+        }
+        panic!("step should be less than {len}", len = width * height);
+    }
+
+    /// Block size of each pass, from coarsest to finest
+    fn block_sizes(&self) -> impl Iterator<Item = usize> {
+        let coarsest = match self {
+            Order::Linear => 1,
+            Order::Progressive => COARSEST_BLOCK,
+        };
+        std::iter::successors(Some(coarsest), |&size| (size > 1).then_some(size / 2))
+    }
+}
+
+/// Number of pixels rendered by the pass with blocks of `size`: those on its grid,
+/// less those on the grid of the pass before, unless it is the `first` pass
+fn pass_len(width: usize, height: usize, size: usize, first: bool) -> usize {
+    let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+    if first {
+        columns * rows
+    } else {
+        // even rows of the grid only have the odd columns, the rest are on the coarser grid
+        rows.div_ceil(2) * (columns / 2) + rows / 2 * columns
+    }
+}
+
+fn pass_block(width: usize, size: usize, first: bool, step: usize) -> Block {
+    let columns = width.div_ceil(size);
+    let (x, y) = if first {
+        xy_from_index(columns, step)
+    } else {
+        // each pair of rows holds `columns / 2` pixels in the even row then `columns` in the odd
+        let (pair, offset) = (
+            step / (columns / 2 + columns),
+            step % (columns / 2 + columns),
+        );
+        if offset < columns / 2 {
+            (offset * 2 + 1, pair * 2)
+        } else {
+            (offset - columns / 2, pair * 2 + 1)
+        }
+    };
+    Block {
+        x: x * size,
+        y: y * size,
+        size,
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Order::Linear => write!(f, "linear"),
+            Order::Progressive => write!(f, "progressive"),
+        }
+    }
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    /// Parses `linear` or `progressive`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Order::Linear),
+            "progressive" => Ok(Order::Progressive),
+            _ => Err(format!("expected linear or progressive, found: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const SIZES: [(usize, usize); 7] = [
+        (1, 1),
+        (1, 40),
+        (40, 1),
+        (17, 3),
+        (37, 23),
+        (64, 64),
+        (0, 5),
+    ];
+
+    #[test]
+    fn every_pixel_is_rendered_once() {
+        for order in [Order::Linear, Order::Progressive] {
+            for (width, height) in SIZES {
+                let passes = order.passes(width, height);
+                assert_eq!(passes.first().map_or(0, |pass| pass.start), 0);
+                assert_eq!(passes.last().map_or(0, |pass| pass.end), width * height);
+                let mut rendered = HashSet::new();
+                for (pass, steps) in passes.iter().enumerate() {
+                    if let Some(next) = passes.get(pass + 1) {
+                        assert_eq!(steps.end, next.start);
+                    }
+                    for step in steps.clone() {
+                        let Block { x, y, size } = order.block(width, height, step);
+                        assert!(
+                            x < width && y < height,
+                            "{order} {width}x{height}: {x}, {y}"
+                        );
+                        // on the grid of its pass, but not of the pass before
+                        assert_eq!((x % size, y % size), (0, 0));
+                        if pass > 0 {
+                            assert!(x % (size * 2) != 0 || y % (size * 2) != 0);
+                        }
+                        assert!(
+                            rendered.insert((x, y)),
+                            "{order} {width}x{height}: {x}, {y} rendered twice"
+                        );
+                    }
+                }
+                assert_eq!(rendered.len(), width * height, "{order} {width}x{height}");
+            }
+        }
+    }
+
+    #[test]
+    fn linear_goes_row_by_row() {
+        let (width, height) = (17, 3);
+        assert_eq!(Order::Linear.passes(width, height), vec![0..width * height]);
+        for step in 0..width * height {
+            let Block { x, y, size } = Order::Linear.block(width, height, step);
+            assert_eq!((x, y, size), (step % width, step / width, 1));
+        }
+    }
+
+    #[test]
+    fn first_pass_covers_the_coarse_grid() {
+        for (width, height) in SIZES {
+            let passes = Order::Progressive.passes(width, height);
+            let first = passes[0]
+                .clone()
+                .map(|step| Order::Progressive.block(width, height, step))
+                .collect::<Vec<_>>();
+            let grid = (0..height)
+                .step_by(COARSEST_BLOCK)
+                .flat_map(|y| {
+                    (0..width).step_by(COARSEST_BLOCK).map(move |x| Block {
+                        x,
+                        y,
+                        size: COARSEST_BLOCK,
+                    })
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(first, grid, "{width}x{height}");
+            // halving down to single pixels
+            assert_eq!(passes.len(), COARSEST_BLOCK.ilog2() as usize + 1);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Text and panels drawn over the viewer's image, with a built-in bitmap font
+
+pub mod font;
+
+/// Horizontal distance between characters, in font pixels
+const ADVANCE: usize = font::WIDTH + 1;
+/// Vertical distance between lines, in font pixels
+const LINE_HEIGHT: usize = font::HEIGHT + 2;
+/// Space between the edge of a panel and its text, in font pixels
+const PADDING: usize = 2;
+
+pub const TEXT_COLOR: u32 = 0xffff_ffff;
+
+/// A buffer of pixels in [`softbuffer_color`](crate::softbuffer_color) format to draw into,
+/// such as a window's surface
+pub struct Frame<'a> {
+    pub pixels: &'a mut [u32],
+    pub width: usize,
+    pub height: usize,
+}
+
+impl<'a> Frame<'a> {
+    pub fn new(pixels: &'a mut [u32], width: usize, height: usize) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "frame should be width x height"
+        );
+        Self {
+            pixels,
+            width,
+            height,
+        }
+    }
+
+    /// Applies `f` to every pixel of the rect, clipped to the frame
+    pub fn map(&mut self, x: isize, y: isize, width: usize, height: usize, f: impl Fn(u32) -> u32) {
+        let left = x.clamp(0, self.width as isize) as usize;
+        let right = (x + width as isize).clamp(0, self.width as isize) as usize;
+        let top = y.clamp(0, self.height as isize) as usize;
+        let bottom = (y + height as isize).clamp(0, self.height as isize) as usize;
+        for row in top..bottom {
+            for pixel in &mut self.pixels[row * self.width + left..row * self.width + right] {
+                *pixel = f(*pixel);
+            }
+        }
+    }
+
+    pub fn fill(&mut self, x: isize, y: isize, width: usize, height: usize, color: u32) {
+        self.map(x, y, width, height, |_| color);
+    }
+
+    /// Darkens the rect to a quarter of its brightness, so text over it stays legible
+    pub fn shade(&mut self, x: isize, y: isize, width: usize, height: usize) {
+        self.map(x, y, width, height, |pixel| {
+            pixel & 0xff00_0000 | (pixel >> 2) & 0x003f_3f3f
+        });
+    }
+
+    /// Draws a one pixel outline just inside the rect
+    pub fn outline(&mut self, x: isize, y: isize, width: usize, height: usize, color: u32) {
+        self.fill(x, y, width, 1, color);
+        self.fill(x, y + height as isize - 1, width, 1, color);
+        self.fill(x, y, 1, height, color);
+        self.fill(x + width as isize - 1, y, 1, height, color);
+    }
+
+    /// Draws one line of `text` with its top-left corner at `(x, y)`,
+    /// each font pixel `scale` pixels square
+    pub fn text(&mut self, x: isize, y: isize, text: &str, scale: usize, color: u32) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + (i * ADVANCE * scale) as isize;
+            for (column, bits) in font::glyph(c).iter().enumerate() {
+                for row in 0..font::HEIGHT {
+                    if bits >> row & 1 != 0 {
+                        self.fill(
+                            left + (column * scale) as isize,
+                            y + (row * scale) as isize,
+                            scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws `lines` on a shaded panel with its top-left corner at `(x, y)`,
+    /// returning the panel's width and height
+    pub fn panel(
+        &mut self,
+        x: isize,
+        y: isize,
+        lines: &[impl AsRef<str>],
+        scale: usize,
+    ) -> (usize, usize) {
+        let (width, height) = panel_size(lines, scale);
+        self.shade(x, y, width, height);
+        for (i, line) in lines.iter().enumerate() {
+            self.text(
+                x + (PADDING * scale) as isize,
+                y + ((PADDING + i * LINE_HEIGHT) * scale) as isize,
+                line.as_ref(),
+                scale,
+                TEXT_COLOR,
+            );
+        }
+        (width, height)
+    }
+}
+
+/// Width and height of one line of `text`
+pub fn text_size(text: &str, scale: usize) -> (usize, usize) {
+    let len = text.chars().count();
+    (
+        (len * ADVANCE).saturating_sub(1) * scale,
+        font::HEIGHT * scale,
+    )
+}
+
+/// Width of each character of a line of text, including the space after it
+pub fn advance(scale: usize) -> usize {
+    ADVANCE * scale
+}
+
+/// Space between the edge of a [`Frame::panel`] and its text
+pub fn padding(scale: usize) -> usize {
+    PADDING * scale
+}
+
+/// Height of each line of a [`Frame::panel`]
+pub fn line_height(scale: usize) -> usize {
+    LINE_HEIGHT * scale
+}
+
+/// Width and height of a [`Frame::panel`] holding `lines`
+pub fn panel_size(lines: &[impl AsRef<str>], scale: usize) -> (usize, usize) {
+    let width = lines
+        .iter()
+        .map(|line| text_size(line.as_ref(), scale).0)
+        .max()
+        .unwrap_or(0);
+    (
+        width + 2 * PADDING * scale,
+        (lines.len() * LINE_HEIGHT + 2 * PADDING - 2) * scale,
+    )
+}
@@ -0,0 +1,157 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// How often long renders report their [`Progress`]
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A snapshot of how far a render has got
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Pixels rendered so far
+    pub done: usize,
+    pub total: usize,
+    pub elapsed: Duration,
+    /// Fraction of the elapsed time each rayon thread has spent rendering, indexed by
+    /// [`rayon::current_thread_index`]
+    pub thread_utilisation: Vec<f64>,
+}
+
+impl Progress {
+    /// Completion in `0..=1`
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.
+        } else {
+            self.done as f64 / self.total as f64
+        }
+    }
+
+    pub fn pixels_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0. {
+            self.done as f64 / secs
+        } else {
+            0.
+        }
+    }
+
+    /// Estimated time remaining at the average rate so far,
+    /// or `None` until the first pixels are done
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.pixels_per_second();
+        (rate > 0.)
+            .then(|| Duration::from_secs_f64(self.total.saturating_sub(self.done) as f64 / rate))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done >= self.total
+    }
+}
+
+impl fmt::Display for Progress {
+    /// e.g. `42.0% (1008000/2400000 px), 9625 px/s, elapsed 1m44s, ETA 2m24s, threads 99% 97%`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{percent:.1}% ({done}/{total} px), {rate:.0} px/s, elapsed {elapsed}, ETA ",
+            percent = self.fraction() * 100.,
+            done = self.done,
+            total = self.total,
+            rate = self.pixels_per_second(),
+            elapsed = FormatDuration(self.elapsed),
+        )?;
+        match self.eta() {
+            Some(eta) => write!(f, "{}", FormatDuration(eta))?,
+            None => write!(f, "-")?,
+        }
+        write!(f, ", threads")?;
+        for utilisation in &self.thread_utilisation {
+            write!(f, " {percent:.0}%", percent = utilisation * 100.)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats as e.g. `1h02m03s`, `2m03s` or `3.4s`
+pub struct FormatDuration(pub Duration);
+
+impl fmt::Display for FormatDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if h > 0 {
+            write!(f, "{h}h{m:02}m{s:02}s")
+        } else if m > 0 {
+            write!(f, "{m}m{s:02}s")
+        } else {
+            write!(f, "{:.1}s", self.0.as_secs_f64())
+        }
+    }
+}
+
+/// Accumulates completed work as it arrives from the render threads
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    started: Instant,
+    last_report: Instant,
+    total: usize,
+    done: usize,
+    busy: Vec<Duration>,
+}
+
+impl ProgressTracker {
+    pub fn new(total: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            last_report: Instant::now(),
+            total,
+            done: 0,
+            busy: vec![Duration::ZERO; rayon::current_num_threads()],
+        }
+    }
+
+    /// Records `pixels` rendered by the rayon thread `thread` in `busy` time
+    pub fn record(&mut self, pixels: usize, thread: usize, busy: Duration) {
+        self.done += pixels;
+        if thread >= self.busy.len() {
+            self.busy.resize(thread + 1, Duration::ZERO);
+        }
+        self.busy[thread] += busy;
+    }
+
+    /// The current progress, if at least `interval` has passed since it was last reported
+    pub fn report(&mut self, interval: Duration) -> Option<Progress> {
+        (self.last_report.elapsed() >= interval).then(|| {
+            self.last_report = Instant::now();
+            self.progress()
+        })
+    }
+
+    /// Same as [`Progress::is_finished`], without taking a snapshot
+    pub fn is_finished(&self) -> bool {
+        self.done >= self.total
+    }
+
+    pub fn progress(&self) -> Progress {
+        let elapsed = self.started.elapsed();
+        let secs = elapsed.as_secs_f64();
+        Progress {
+            done: self.done,
+            total: self.total,
+            elapsed,
+            thread_utilisation: self
+                .busy
+                .iter()
+                .map(|busy| {
+                    if secs > 0. {
+                        (busy.as_secs_f64() / secs).min(1.)
+                    } else {
+                        0.
+                    }
+                })
+                .collect(),
+        }
+    }
+}
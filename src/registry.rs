@@ -0,0 +1,32 @@
+//! Looks up artworks by name, so that callers can pick an [`Art`] at runtime
+//! and still call the generic functions of this crate.
+
+use crate::{art, Art};
+
+/// Something to do with an artwork, once its type is known
+pub trait Visitor {
+    type Output;
+    fn visit<Artwork: Art>(self) -> Self::Output;
+}
+
+macro_rules! registry {
+    ( $($artwork:ty),* $(,)? ) => {
+        /// Names of every registered artwork, in registration order
+        pub const NAMES: &[&str] = &[$(<$artwork as Art>::NAME),*];
+
+        /// Calls `visitor` with the artwork called `name`, if there is one
+        pub fn visit<V: Visitor>(name: &str, visitor: V) -> Option<V::Output> {
+            $(
+                if name == <$artwork as Art>::NAME {
+                    return Some(visitor.visit::<$artwork>());
+                }
+            )*
+            None
+        }
+    };
+}
+
+registry! {
+    art::strawberries::Artwork,
+    art::sunflower_field::Artwork,
+}
@@ -0,0 +1,639 @@
+use std::{
+    cmp::Reverse,
+    io,
+    path::Path,
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    canvas::Rect,
+    checkpoint::Checkpoint,
+    image::{FloatImage, Image},
+    job::{Control, RenderJob},
+    order::{Block, Order},
+    progress::{Progress, ProgressTracker, PROGRESS_INTERVAL},
+    utils::*,
+    Art, Canvas, Sampling,
+};
+
+pub const BATCH_SIZE: usize = 32;
+
+/// Placeholder for pixels that have not been rendered yet.
+/// Displays as white, but has a zero alpha, so it can never be a colour from [`softbuffer_color`].
+pub const UNRENDERED: u32 = 0x00ff_ffff;
+
+#[derive(Debug)]
+pub struct PixelReady {
+    /// Step of the first pixel, see [`Order::block`]
+    pub index: usize,
+    /// Consecutive steps from `index`, or [`UNRENDERED`] past the end of a pass
+    pub pixels: [u32; BATCH_SIZE],
+    /// The rayon thread that rendered the batch, see [`rayon::current_thread_index`]
+    pub thread: usize,
+    /// How long the batch took to render
+    pub busy: Duration,
+    /// How long each pixel took to render, if [`RenderOptions::timed`]
+    pub times: Option<Box<[Duration; BATCH_SIZE]>>,
+}
+
+impl PixelReady {
+    /// Copies the batch into `image`, a `width` x `height` buffer being rendered in `order`,
+    /// filling the block of each pixel. Returns the number of pixels rendered.
+    pub fn paste(&self, order: Order, width: usize, height: usize, image: &mut [u32]) -> usize {
+        self.fill(order, width, height, image, |offset| self.pixels[offset])
+    }
+
+    /// Like [`PixelReady::paste`], for the seconds each pixel took to render, if they were
+    /// recorded
+    pub fn paste_times(&self, order: Order, width: usize, height: usize, times: &mut [f32]) {
+        if let Some(pixel_times) = &self.times {
+            self.fill(order, width, height, times, |offset| {
+                pixel_times[offset].as_secs_f32()
+            });
+        }
+    }
+
+    /// Fills the block of each pixel of the batch in `buffer` with `value(offset)`
+    fn fill<T: Copy>(
+        &self,
+        order: Order,
+        width: usize,
+        height: usize,
+        buffer: &mut [T],
+        value: impl Fn(usize) -> T,
+    ) -> usize {
+        let mut rendered = 0;
+        for (offset, &pixel) in self.pixels.iter().enumerate() {
+            if pixel == UNRENDERED {
+                continue;
+            }
+            let value = value(offset);
+            let Block { x, y, size } = order.block(width, height, self.index + offset);
+            for row in y..(y + size).min(height) {
+                buffer[row * width + x..row * width + (x + size).min(width)].fill(value);
+            }
+            rendered += 1;
+        }
+        rendered
+    }
+}
+
+#[derive(Debug)]
+pub struct TileReady {
+    /// Index of the tile in the list passed to [`render_tiles`]
+    pub index: usize,
+    pub rect: Rect,
+    pub pixels: Vec<(u8, u8, u8)>,
+    /// The rayon thread that rendered the tile, see [`rayon::current_thread_index`]
+    pub thread: usize,
+    /// How long the tile took to render
+    pub busy: Duration,
+}
+
+/// What to render and how, shared by the viewer and the headless renderer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    pub canvas: Canvas,
+    pub sampling: Sampling,
+    /// Only affects [`render_batches`], as the rest render in one go or in tiles
+    pub order: Order,
+    /// Whether to record how long each pixel takes to render, in [`PixelReady::times`].
+    /// Like `order`, only affects [`render_batches`].
+    pub timed: bool,
+}
+
+impl RenderOptions {
+    /// The artwork's native canvas, with one sample per pixel
+    pub fn native<Artwork: Art>() -> Self {
+        Self {
+            canvas: Canvas::native::<Artwork>(),
+            sampling: Sampling::default(),
+            order: Order::default(),
+            timed: false,
+        }
+    }
+}
+
+/// Renders every pixel of the canvas in batches of [`BATCH_SIZE`] across the rayon thread pool,
+/// in [`RenderOptions::order`], sending each batch to `tx` as soon as it is ready.
+/// Each pass of the order is sent in full before the next starts, so receivers using
+/// [`PixelReady::paste`] never overwrite a finer block with a coarser one.
+/// Stops early once the receiver is dropped.
+pub fn render_batches<Artwork: Art>(options: RenderOptions, tx: Sender<PixelReady>) {
+    render_region_batches::<Artwork>(options.canvas.rect(), options, tx);
+}
+
+/// Like [`render_batches`], but only for the pixels inside `rect`,
+/// with [`PixelReady::index`] counting steps within it
+pub fn render_region_batches<Artwork: Art>(
+    rect: Rect,
+    options: RenderOptions,
+    tx: Sender<PixelReady>,
+) {
+    for pass in options.order.passes(rect.width, rect.height) {
+        let result = (0..pass.len().div_ceil(BATCH_SIZE))
+            .into_par_iter()
+            .try_for_each_with(tx.clone(), |tx, counter| {
+                let index = pass.start + counter * BATCH_SIZE;
+                tx.send(render_batch::<Artwork>(rect, &options, index, pass.end))
+                    .map_err(|_| ())
+            });
+        // an error only means the receiver is gone, so nobody wants the remaining batches
+        if result.is_err() {
+            return;
+        }
+    }
+}
+
+/// The part of the canvas being looked at, shared with [`render_region_batches_prioritised`]
+/// so it can render that part first, even as it moves
+#[derive(Debug, Clone, Default)]
+pub struct Viewport(Arc<Mutex<Option<Rect>>>);
+
+impl Viewport {
+    pub fn set(&self, rect: Rect) {
+        *self.0.lock().unwrap() = Some(rect);
+    }
+
+    pub fn get(&self) -> Option<Rect> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Like [`render_region_batches`], but each rayon thread takes the batch nearest to `viewport`
+/// from a shared queue, which is re-sorted whenever the viewport moves.
+/// See [`RenderJob`] for a render that can also be paused and cancelled.
+pub fn render_region_batches_prioritised<Artwork: Art>(
+    rect: Rect,
+    options: RenderOptions,
+    viewport: &Viewport,
+    tx: Sender<PixelReady>,
+) {
+    render_controlled::<Artwork>(rect, options, viewport, &Control::default(), tx);
+}
+
+/// [`render_region_batches_prioritised`] under `control`: the rayon threads stop taking batches
+/// while it is paused, waiting on this thread to resume, and stop for good once it is cancelled
+pub(crate) fn render_controlled<Artwork: Art>(
+    rect: Rect,
+    options: RenderOptions,
+    viewport: &Viewport,
+    control: &Control,
+    tx: Sender<PixelReady>,
+) {
+    for pass in options.order.passes(rect.width, rect.height) {
+        let queue = Mutex::new(BatchQueue {
+            batches: (pass.start..pass.end).step_by(BATCH_SIZE).rev().collect(),
+            viewport: None,
+        });
+        loop {
+            rayon::scope(|scope| {
+                for _ in 0..rayon::current_num_threads() {
+                    let (queue, tx) = (&queue, tx.clone());
+                    scope.spawn(move |_| loop {
+                        if !control.is_running() {
+                            break;
+                        }
+                        let index = {
+                            let mut queue = queue.lock().unwrap();
+                            queue.prioritise(viewport.get(), rect, options.order);
+                            queue.batches.pop()
+                        };
+                        let Some(index) = index else {
+                            break;
+                        };
+                        if tx
+                            .send(render_batch::<Artwork>(rect, &options, index, pass.end))
+                            .is_err()
+                        {
+                            // the receiver is gone, so nobody wants the remaining batches
+                            control.cancel();
+                        }
+                    });
+                }
+            });
+            if queue.lock().unwrap().batches.is_empty() {
+                break;
+            }
+            if !control.wait_while_paused() {
+                return;
+            }
+        }
+    }
+    control.finish();
+}
+
+/// Batches of a pass still to render, highest priority last
+struct BatchQueue {
+    batches: Vec<usize>,
+    /// The viewport `batches` was sorted for
+    viewport: Option<Rect>,
+}
+
+impl BatchQueue {
+    /// Sorts the batches by the distance of their first pixel from `viewport`,
+    /// if it has moved since the last sort
+    fn prioritise(&mut self, viewport: Option<Rect>, rect: Rect, order: Order) {
+        if viewport == self.viewport {
+            return;
+        }
+        self.viewport = viewport;
+        let Some(viewport) = viewport else {
+            return;
+        };
+        let distance = |index: usize| {
+            let Block { x, y, .. } = order.block(rect.width, rect.height, index);
+            let (x, y) = (rect.x + x, rect.y + y);
+            let dx = viewport
+                .x
+                .saturating_sub(x)
+                .max((x + 1).saturating_sub(viewport.x + viewport.width));
+            let dy = viewport
+                .y
+                .saturating_sub(y)
+                .max((y + 1).saturating_sub(viewport.y + viewport.height));
+            dx.max(dy)
+        };
+        self.batches
+            .sort_by_cached_key(|&index| Reverse((distance(index), index)));
+    }
+}
+
+/// Renders the steps from `index` to at most `end`, see [`PixelReady`]
+fn render_batch<Artwork: Art>(
+    rect: Rect,
+    options: &RenderOptions,
+    index: usize,
+    end: usize,
+) -> PixelReady {
+    let RenderOptions {
+        canvas,
+        sampling,
+        order,
+        timed,
+    } = options;
+    let started = Instant::now();
+    let mut pixels = [UNRENDERED; BATCH_SIZE];
+    let mut times = timed.then(|| Box::new([Duration::ZERO; BATCH_SIZE]));
+    for (offset, pixel) in pixels.iter_mut().enumerate().take(end - index) {
+        let Block { x, y, .. } = order.block(rect.width, rect.height, index + offset);
+        let pixel_started = times.is_some().then(Instant::now);
+        let rgb = sampling.draw::<Artwork>(canvas, rect.x + x, rect.y + y);
+        if let (Some(times), Some(pixel_started)) = (&mut times, pixel_started) {
+            times[offset] = pixel_started.elapsed();
+        }
+        *pixel = softbuffer_color(rgb);
+    }
+
+    // debug_print_stored_values();
+
+    PixelReady {
+        index,
+        pixels,
+        thread: rayon::current_thread_index().unwrap_or_default(),
+        busy: started.elapsed(),
+        times,
+    }
+}
+
+/// Renders the whole of `Artwork` without opening a window
+pub fn render<Artwork: Art>(options: RenderOptions) -> Image {
+    render_with_progress::<Artwork>(options, |_| {})
+}
+
+/// Like [`render`], calling `on_progress` every [`PROGRESS_INTERVAL`] and once more at the end
+pub fn render_with_progress<Artwork: Art>(
+    options: RenderOptions,
+    on_progress: impl FnMut(&Progress),
+) -> Image {
+    render_with_deadline::<Artwork>(options, None, on_progress)
+        .expect("a render without a deadline should finish")
+}
+
+/// Like [`render_with_progress`], but cancels the render at `deadline`, returning `None`
+pub fn render_with_deadline<Artwork: Art>(
+    options: RenderOptions,
+    deadline: Option<Instant>,
+    on_progress: impl FnMut(&Progress),
+) -> Option<Image> {
+    render_until::<Artwork>(options, deadline, on_progress, None)
+}
+
+/// Like [`render_with_deadline`], also returning the seconds each pixel took to render,
+/// e.g. for a [`heatmap`](crate::heatmap) of where the formula is expensive
+pub fn render_timed<Artwork: Art>(
+    options: RenderOptions,
+    deadline: Option<Instant>,
+    on_progress: impl FnMut(&Progress),
+) -> Option<(Image, Image<f64>)> {
+    let canvas = options.canvas;
+    let options = RenderOptions {
+        timed: true,
+        ..options
+    };
+    let mut times = vec![0.; canvas.len()];
+    let image = render_until::<Artwork>(options, deadline, on_progress, Some(&mut times))?;
+    let times = times.into_iter().map(f64::from).collect();
+    Some((image, Image::new(canvas.width, canvas.height, times)))
+}
+
+/// [`render_with_deadline`], pasting the seconds each pixel took into `times` if given and
+/// [`RenderOptions::timed`]
+fn render_until<Artwork: Art>(
+    options: RenderOptions,
+    deadline: Option<Instant>,
+    mut on_progress: impl FnMut(&Progress),
+    mut times: Option<&mut [f32]>,
+) -> Option<Image> {
+    let canvas = options.canvas;
+    let (job, rx) = RenderJob::start::<Artwork>(canvas.rect(), options, Viewport::default());
+
+    let mut image = vec![UNRENDERED; canvas.len()];
+    let mut tracker = ProgressTracker::new(image.len());
+    loop {
+        let Some(timeout) = next_wait(deadline) else {
+            job.cancel();
+            return None;
+        };
+        match rx.recv_timeout(timeout) {
+            Ok(ready) => {
+                let rendered = ready.paste(options.order, canvas.width, canvas.height, &mut image);
+                tracker.record(rendered, ready.thread, ready.busy);
+                if let Some(times) = times.as_deref_mut() {
+                    ready.paste_times(options.order, canvas.width, canvas.height, times);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // the channel closes once every batch has been sent
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if let Some(progress) = tracker.report(PROGRESS_INTERVAL) {
+            on_progress(&progress);
+        }
+    }
+    on_progress(&tracker.progress());
+
+    Some(Image::from_softbuffer(canvas.width, canvas.height, &image))
+}
+
+/// How long to wait for more pixels before reporting progress again,
+/// or `None` once `deadline` has passed
+fn next_wait(deadline: Option<Instant>) -> Option<Duration> {
+    match deadline {
+        Some(deadline) => deadline
+            .checked_duration_since(Instant::now())
+            .filter(|left| !left.is_zero())
+            .map(|left| left.min(PROGRESS_INTERVAL)),
+        None => Some(PROGRESS_INTERVAL),
+    }
+}
+
+/// Renders each of `tiles` across the rayon thread pool,
+/// sending each tile to `tx` as soon as it is ready.
+/// Stops early once `control` is cancelled or the receiver is dropped.
+pub(crate) fn render_tiles<Artwork: Art>(
+    options: RenderOptions,
+    tiles: Vec<(usize, Rect)>,
+    control: &Control,
+    tx: Sender<TileReady>,
+) {
+    // an error only means nobody wants the remaining tiles
+    let _ = tiles
+        .into_par_iter()
+        .try_for_each_with(tx, |tx, (index, rect)| {
+            if !control.is_running() {
+                return Err(());
+            }
+            let started = Instant::now();
+            let pixels = render_tile::<Artwork>(&options, rect);
+            tx.send(TileReady {
+                index,
+                rect,
+                pixels,
+                thread: rayon::current_thread_index().unwrap_or_default(),
+                busy: started.elapsed(),
+            })
+            .map_err(|_| ())
+        });
+}
+
+fn render_tile<Artwork: Art>(options: &RenderOptions, rect: Rect) -> Vec<(u8, u8, u8)> {
+    (0..rect.len())
+        .map(|index| draw_in_rect::<Artwork>(options, rect, index))
+        .collect()
+}
+
+/// Draws the `index`th pixel of `rect`, counting row by row
+#[inline(always)]
+fn draw_in_rect<Artwork: Art>(options: &RenderOptions, rect: Rect, index: usize) -> (u8, u8, u8) {
+    let (x, y) = xy_from_index(rect.width, index);
+    options
+        .sampling
+        .draw::<Artwork>(&options.canvas, rect.x + x, rect.y + y)
+}
+
+/// Renders only the pixels of the canvas inside `rect`, row by row, across the rayon thread pool.
+/// Useful for iterating on one part of an artwork without waiting for the whole canvas.
+pub fn render_region<Artwork: Art>(rect: Rect, options: RenderOptions) -> Vec<(u8, u8, u8)> {
+    render_region_with_deadline::<Artwork>(rect, options, None)
+        .expect("a render without a deadline should finish")
+}
+
+/// Like [`render_region`], but cancels the render at `deadline`, returning `None`
+pub fn render_region_with_deadline<Artwork: Art>(
+    rect: Rect,
+    options: RenderOptions,
+    deadline: Option<Instant>,
+) -> Option<Vec<(u8, u8, u8)>> {
+    assert!(
+        options.canvas.rect().contains(&rect),
+        "region {rect:?} should be inside the canvas {canvas:?}",
+        canvas = options.canvas
+    );
+    until_deadline(deadline, |control| {
+        render_pixels(rect.len(), control, |index| {
+            draw_in_rect::<Artwork>(&options, rect, index)
+        })
+    })
+}
+
+/// Like [`render_region`], but returns the channel values before they are quantised,
+/// see [`Art::channels_xy`]
+pub fn render_region_channels<Artwork: Art>(
+    rect: Rect,
+    options: RenderOptions,
+) -> Vec<(f64, f64, f64)> {
+    render_region_channels_with_deadline::<Artwork>(rect, options, None)
+        .expect("a render without a deadline should finish")
+}
+
+/// Like [`render_region_channels`], but cancels the render at `deadline`, returning `None`
+pub fn render_region_channels_with_deadline<Artwork: Art>(
+    rect: Rect,
+    options: RenderOptions,
+    deadline: Option<Instant>,
+) -> Option<Vec<(f64, f64, f64)>> {
+    assert!(
+        options.canvas.rect().contains(&rect),
+        "region {rect:?} should be inside the canvas {canvas:?}",
+        canvas = options.canvas
+    );
+    until_deadline(deadline, |control| {
+        render_pixels(rect.len(), control, |index| {
+            let (x, y) = xy_from_index(rect.width, index);
+            options
+                .sampling
+                .channels::<Artwork>(&options.canvas, rect.x + x, rect.y + y)
+        })
+    })
+}
+
+/// Runs `render` on another thread, cancelling its [`Control`] if it is still running at
+/// `deadline`. `render` should then stop early and return `None`.
+fn until_deadline<T: Send>(
+    deadline: Option<Instant>,
+    render: impl FnOnce(&Control) -> Option<T> + Send,
+) -> Option<T> {
+    let control = Control::default();
+    std::thread::scope(|scope| {
+        let rendering = scope.spawn(|| {
+            let result = render(&control);
+            control.finish();
+            result
+        });
+        if let Some(deadline) = deadline {
+            if control.wait_until(deadline) {
+                control.cancel();
+            }
+        }
+        rendering
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Evaluates `pixel` for the indices `0..len` across the rayon thread pool, in batches of
+/// [`BATCH_SIZE`], or returns `None` if `control` is cancelled first
+fn render_pixels<T: Send>(
+    len: usize,
+    control: &Control,
+    pixel: impl Fn(usize) -> T + Sync,
+) -> Option<Vec<T>> {
+    let batches = (0..len.div_ceil(BATCH_SIZE))
+        .into_par_iter()
+        .map(|batch| {
+            let indices = batch * BATCH_SIZE..((batch + 1) * BATCH_SIZE).min(len);
+            control
+                .is_running()
+                .then(|| indices.map(&pixel).collect::<Vec<_>>())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(batches.into_iter().flatten().collect())
+}
+
+/// Renders the whole of `Artwork` as channel values before they are quantised,
+/// e.g. for inspecting clipping and banding
+pub fn render_channels<Artwork: Art>(options: RenderOptions) -> FloatImage {
+    let canvas = options.canvas;
+    let pixels = render_region_channels::<Artwork>(canvas.rect(), options);
+    Image::new(canvas.width, canvas.height, pixels)
+}
+
+/// Renders the whole of `Artwork` in tiles of `tile_size`, saving completed tiles to
+/// `checkpoint_dir` as it goes. If the directory already holds a checkpoint for the same
+/// settings, the tiles in it are loaded instead of being rendered again.
+/// Calls `on_progress` every [`PROGRESS_INTERVAL`] and once more at the end,
+/// counting only the tiles that still need rendering.
+/// Returns the image and how many of its tiles were loaded from the checkpoint.
+pub fn render_resumable<Artwork: Art>(
+    options: RenderOptions,
+    tile_size: usize,
+    checkpoint_dir: &Path,
+    on_progress: impl FnMut(&Progress),
+) -> io::Result<(Image, usize)> {
+    let rendered = render_resumable_with_deadline::<Artwork>(
+        options,
+        tile_size,
+        checkpoint_dir,
+        None,
+        on_progress,
+    )?;
+    Ok(rendered.expect("a render without a deadline should finish"))
+}
+
+/// Like [`render_resumable`], but cancels the render at `deadline`, returning `None` once the
+/// tiles completed so far are saved to the checkpoint, so a later render can resume from them
+pub fn render_resumable_with_deadline<Artwork: Art>(
+    options: RenderOptions,
+    tile_size: usize,
+    checkpoint_dir: &Path,
+    deadline: Option<Instant>,
+    mut on_progress: impl FnMut(&Progress),
+) -> io::Result<Option<(Image, usize)>> {
+    let canvas = options.canvas;
+    let tiles = canvas.tiles(tile_size);
+    let mut checkpoint = Checkpoint::open::<Artwork>(checkpoint_dir, &options, tile_size)?;
+
+    let mut image = Image::new(canvas.width, canvas.height, vec![(0, 0, 0); canvas.len()]);
+    let mut resumed = 0;
+    for index in checkpoint.done().collect::<Vec<_>>() {
+        let rect = *tiles.get(index).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("no tile {index}"))
+        })?;
+        image.paste(rect, &checkpoint.load(index, rect.len())?);
+        resumed += 1;
+    }
+
+    let remaining = tiles
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(index, _)| !checkpoint.is_done(*index))
+        .collect::<Vec<_>>();
+    let mut tracker = ProgressTracker::new(remaining.iter().map(|(_, rect)| rect.len()).sum());
+    let (tx, rx) = mpsc::channel();
+    let control = Arc::new(Control::default());
+    let tiles_control = control.clone();
+    rayon::spawn(move || render_tiles::<Artwork>(options, remaining, &tiles_control, tx));
+
+    loop {
+        let Some(timeout) = next_wait(deadline) else {
+            control.cancel();
+            checkpoint.save()?;
+            return Ok(None);
+        };
+        match rx.recv_timeout(timeout) {
+            Ok(TileReady {
+                index,
+                rect,
+                pixels,
+                thread,
+                busy,
+            }) => {
+                image.paste(rect, &pixels);
+                if let Err(err) = checkpoint.complete(index, &pixels) {
+                    control.cancel();
+                    return Err(err);
+                }
+                tracker.record(rect.len(), thread, busy);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // the channel closes once every tile has been sent
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if let Some(progress) = tracker.report(PROGRESS_INTERVAL) {
+            on_progress(&progress);
+        }
+    }
+    control.finish();
+    checkpoint.save()?;
+    on_progress(&tracker.progress());
+
+    Ok(Some((image, resumed)))
+}
@@ -1,32 +1,115 @@
 use std::{
-    fmt::Write as _,
     num::NonZeroU32,
+    path::PathBuf,
     rc::Rc,
-    sync::mpsc::{self, Receiver},
-    time::{Duration, Instant},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use crate::{utils::*, winit_app, Art};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use crate::{
+    diff::LISTED_DIFFERENCES,
+    image::Image,
+    job::RenderJob,
+    overlay::Frame,
+    progress::{FormatDuration, ProgressTracker, PROGRESS_INTERVAL},
+    render::*,
+    rgb_from_softbuffer_color,
+    trace::{trace, Trace},
+    winit_app, Art, Canvas, Rect,
+};
 use softbuffer::Surface;
-use track::{set_should_track, with_stack};
 use winit::{
-    event::{ElementState, Event, KeyEvent, MouseButton, WindowEvent},
+    dpi::PhysicalSize,
+    event::{ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     keyboard::{Key, ModifiersState, NamedKey},
     window::Window,
 };
 
+mod channel;
+mod fit;
+mod hover;
+mod hud;
+mod loupe;
+mod reference;
+mod srgb;
+mod times;
+mod trace_panel;
+
+use channel::Channel;
+use fit::{Fit, Placement};
+use hover::Hover;
+use loupe::Loupe;
+use reference::Reference;
+use times::TimeMap;
+use trace_panel::TracePanel;
+
+/// Zoom factor for one step of the mouse wheel or the `+` and `-` keys
+const ZOOM_STEP: f64 = std::f64::consts::SQRT_2;
+
+/// Limits that keep the zoomed canvas within the range of `scroll_x` and `scroll_y`
+const MIN_ZOOM: f64 = 1. / 16.;
+const MAX_ZOOM: f64 = 100_000.;
+
+/// How often to redraw the scaled image while pixels are still arriving
+const RESAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
 struct State {
     pane: Pane,
     key_modifiers: ModifiersState,
+    last_tick: Instant,
+    /// Whether to draw the [`hud`]
+    hud: bool,
+    /// The channel shown, applied as the image is drawn so switching needs no re-render
+    channel: Channel,
+    /// What to show in place of the image, if anything
+    shown: Shown,
+    mouse: Mouse,
+    /// Every artwork in the session, cycled with Tab
+    artworks: Vec<ViewedArtwork>,
+    /// Index of the artwork shown
+    current: usize,
+}
+
+/// An artwork in the viewer, with its render and how it is being viewed, which are kept while
+/// other artworks are shown so switching back picks up where it left off
+pub struct ViewedArtwork {
+    /// [`handle`] for this artwork, so the viewer can hold artworks of different types
+    handle: fn(&mut State, Event<()>, &ActiveEventLoop),
+    title: &'static str,
+    /// The starting canvas
+    canvas: Canvas,
+    /// The canvas being rendered, which is the starting canvas scaled by `zoom`
+    options: RenderOptions,
+    zoom: f64,
+    /// The part of `options.canvas` held in `image`
+    rect: Rect,
     image: Vec<u32>,
+    /// Seconds each pixel of `image` took to render, or NaN until it is rendered,
+    /// empty unless `options.timed`
+    times: Vec<f32>,
+    time_map: TimeMap,
+    /// How `image` is scaled to the window
+    fit: Fit,
+    scaled: Scaled,
+    /// Rendering `rect`, once the artwork has first been shown, and paused while it is hidden
+    job: Option<RenderJob>,
     rx: Receiver<PixelReady>,
-    last_tick: Instant,
+    /// The visible part of `options.canvas`, which is rendered first
+    viewport: Viewport,
     time_started: Instant,
-    finished: bool,
-    drawn: usize,
-    mouse: Mouse,
+    finished_in: Option<Duration>,
+    progress: ProgressTracker,
+    /// Traces of clicked pixels, computed on other threads
+    trace_tx: Sender<TracePanel>,
+    trace_rx: Receiver<TracePanel>,
+    trace_panel: Option<TracePanel>,
+    hover: Hover,
+    loupe: Loupe,
+    reference: Option<Reference>,
     scroll_x: i32,
     scroll_y: i32,
 }
@@ -38,20 +121,76 @@ struct Pane<D = Rc<Window>, W = D> {
 }
 
 impl Pane {
-    fn new(elwt: &ActiveEventLoop) -> Self {
-        let window = winit_app::make_window(elwt, |w| w);
+    fn new(elwt: &ActiveEventLoop, title: &str) -> Self {
+        let window = winit_app::make_window(elwt, |w| w.with_title(title));
         let context = softbuffer::Context::new(window.clone()).unwrap();
         let surface = Surface::new(&context, window.clone()).unwrap();
         Self { window, surface }
     }
 }
 
-const BATCH_SIZE: usize = 32;
+/// `image` as last drawn to fit the window by [`fit::resample`], which is redone when the
+/// window or the placement changes, and every [`RESAMPLE_INTERVAL`] while pixels arrive
+#[derive(Default)]
+struct Scaled {
+    pixels: Vec<u32>,
+    /// The placement, window size and what was shown, that it was drawn for
+    drawn_for: Option<(Placement, usize, usize, Shown)>,
+    drawn_at: Option<Instant>,
+    /// Whether `image` has changed since
+    stale: bool,
+}
+
+impl Scaled {
+    fn update(
+        &mut self,
+        image: &[u32],
+        rect: Rect,
+        placement: Placement,
+        (width, height): (usize, usize),
+        shown: Shown,
+    ) {
+        let moved = self.drawn_for != Some((placement, width, height, shown));
+        let due = self
+            .drawn_at
+            .is_none_or(|drawn_at| drawn_at.elapsed() >= RESAMPLE_INTERVAL);
+        if moved || (self.stale && due) {
+            self.pixels.resize(width * height, 0);
+            fit::resample(image, rect, placement, &mut self.pixels, width);
+            self.drawn_for = Some((placement, width, height, shown));
+            self.drawn_at = Some(Instant::now());
+            self.stale = false;
+        }
+    }
+}
+
+/// What is drawn in place of the rendered image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shown {
+    Image,
+    /// Differences from the reference render, toggled with `d`
+    Differences,
+    /// How long each pixel took to render, toggled with `p`
+    Times,
+}
+
+impl Shown {
+    /// Shows `shown`, or the image again if it is already shown
+    fn toggle(self, shown: Self) -> Self {
+        if self == shown {
+            Self::Image
+        } else {
+            shown
+        }
+    }
+}
 
-#[derive(Debug)]
-struct PixelReady {
-    index: usize,
-    pixels: [u32; BATCH_SIZE],
+/// A zoom requested by an event, applied once the event has been handled
+enum Zoom {
+    /// Zoom by `factor` about the window point `(x, y)`
+    By { factor: f64, x: f64, y: f64 },
+    /// Back to the starting canvas, scrolled to the top left
+    Reset,
 }
 
 struct Mouse {
@@ -63,6 +202,16 @@ struct Mouse {
     middle_state: ElementState,
 }
 
+impl Mouse {
+    /// The pixel of `canvas` under the cursor, if any, when it is placed by `placement`
+    fn pixel(&self, canvas: Canvas, placement: Placement) -> Option<(usize, usize)> {
+        let (x, y) = placement.canvas_at(self.x, self.y);
+        let (x, y) = (x.floor(), y.floor());
+        (x >= 0. && y >= 0. && x < canvas.width as f64 && y < canvas.height as f64)
+            .then_some((x as usize, y as usize))
+    }
+}
+
 impl Default for Mouse {
     fn default() -> Self {
         Self {
@@ -76,263 +225,556 @@ impl Default for Mouse {
     }
 }
 
-pub fn run<Artwork: Art>() {
-    let event_loop = EventLoop::new().unwrap();
+impl ViewedArtwork {
+    pub fn new<Artwork: Art>(options: RenderOptions) -> Self {
+        let canvas = options.canvas;
+        let (trace_tx, trace_rx) = mpsc::channel();
+        Self {
+            handle: handle::<Artwork>,
+            title: Artwork::TITLE,
+            canvas,
+            options,
+            zoom: 1.,
+            rect: canvas.rect(),
+            image: vec![UNRENDERED; canvas.len()],
+            times: Vec::new(),
+            time_map: TimeMap::default(),
+            fit: Fit::Actual,
+            scaled: Scaled::default(),
+            job: None,
+            rx: mpsc::channel().1,
+            viewport: Viewport::default(),
+            time_started: Instant::now(),
+            finished_in: None,
+            progress: ProgressTracker::new(canvas.len()),
+            trace_tx,
+            trace_rx,
+            trace_panel: None,
+            hover: Hover::new(),
+            loupe: Loupe::new(),
+            reference: None,
+            scroll_x: 0,
+            scroll_y: 0,
+        }
+    }
 
-    let app = winit_app::WinitAppBuilder::with_init(move |elwt| {
-        let image = vec![u32::MAX; Artwork::FULL_M * Artwork::FULL_N];
-
-        let (tx, rx) = mpsc::channel();
-
-        rayon::spawn(move || {
-            (0..((Artwork::FULL_M * Artwork::FULL_N).div_ceil(BATCH_SIZE)))
-                .into_par_iter()
-                .for_each_with(tx, |tx, counter| {
-                    let mut pixels = [u32::MAX; BATCH_SIZE];
-                    let index = counter * BATCH_SIZE;
-                    for (offset, pixel) in pixels.iter_mut().enumerate() {
-                        let (x, y) = xy_from_index(Artwork::FULL_M, index + offset);
-                        let m = (x + 1) as f64;
-                        let n = (y + 1) as f64;
-                        let rgb = Artwork::draw(m, n);
-                        *pixel = softbuffer_color(rgb);
-                    }
+    /// Compares the render with `reference`, a render of the starting canvas,
+    /// e.g. of another implementation of the artwork
+    pub fn with_reference(self, reference: Arc<Image>) -> Self {
+        Self {
+            reference: Some(Reference::new(reference)),
+            ..self
+        }
+    }
 
-                    // debug_print_stored_values();
+    /// Marks the image as changed, so it is scaled, compared and timed again
+    fn changed(&mut self) {
+        self.scaled.stale = true;
+        self.time_map.stale = true;
+        if let Some(reference) = &mut self.reference {
+            reference.stale = true;
+        }
+    }
 
-                    if tx.send(PixelReady { index, pixels }).is_err() {
-                        eprintln!("loop no longer exists");
-                    }
-                });
-        });
+    /// Pastes the pixels that have arrived, returning whether that finished the render
+    fn receive(&mut self) -> bool {
+        while let Ok(ready) = self.rx.try_recv() {
+            let (width, height) = (self.rect.width, self.rect.height);
+            let rendered = ready.paste(self.options.order, width, height, &mut self.image);
+            if self.options.timed {
+                ready.paste_times(self.options.order, width, height, &mut self.times);
+            }
+            self.progress.record(rendered, ready.thread, ready.busy);
+            self.changed();
+        }
+        let finished =
+            self.job.is_some() && self.finished_in.is_none() && self.progress.is_finished();
+        if finished {
+            self.finished_in = Some(self.time_started.elapsed());
+        }
+        finished
+    }
+
+    /// Starts rendering `rect` of `options.canvas` in place of the current render, which is
+    /// cancelled. Until they are rendered, pixels show the nearest pixel of
+    /// the previous render, so zooming shows the old image scaled as a placeholder.
+    fn render<Artwork: Art>(&mut self, options: RenderOptions, rect: Rect) {
+        let (old_canvas, old_rect) = (self.options.canvas, self.rect);
+        let scale_x = old_canvas.width as f64 / options.canvas.width as f64;
+        let scale_y = old_canvas.height as f64 / options.canvas.height as f64;
+        let image = rect
+            .pixels()
+            .map(|(x, y)| {
+                let old_x = ((x as f64 + 0.5) * scale_x) as usize;
+                let old_y = ((y as f64 + 0.5) * scale_y) as usize;
+                if old_rect.contains(&Rect::new(old_x, old_y, 1, 1)) {
+                    self.image[(old_y - old_rect.y) * old_rect.width + old_x - old_rect.x]
+                } else {
+                    UNRENDERED
+                }
+            })
+            .collect();
+
+        let rx = match &mut self.job {
+            Some(job) => job.restart(rect, options),
+            None => {
+                let (job, rx) = RenderJob::start::<Artwork>(rect, options, self.viewport.clone());
+                self.job = Some(job);
+                rx
+            }
+        };
+
+        self.options = options;
+        self.rect = rect;
+        self.image = image;
+        self.times = match options.timed {
+            true => vec![f32::NAN; rect.len()],
+            false => Vec::new(),
+        };
+        self.changed();
+        self.rx = rx;
+        self.time_started = Instant::now();
+        self.finished_in = None;
+        self.progress = ProgressTracker::new(rect.len());
+    }
+
+    /// Starts recording how long each pixel takes to render, if it is not already, re-rendering
+    /// as the pixels rendered so far were not timed
+    fn time_pixels<Artwork: Art>(&mut self) {
+        if !self.options.timed {
+            let options = RenderOptions {
+                timed: true,
+                ..self.options
+            };
+            self.render::<Artwork>(options, self.rect);
+        }
+    }
+
+    /// Pauses rendering while the artwork is hidden
+    fn pause(&self) {
+        if let Some(job) = &self.job {
+            job.pause();
+        }
+    }
 
+    fn resume(&self) {
+        if let Some(job) = &self.job {
+            job.resume();
+        }
+    }
+
+    /// Zooms to `zoom` times the starting canvas, keeping the same part of the artwork under
+    /// the window point `(x, y)`, and re-renders at the new scale
+    fn zoom<Artwork: Art>(&mut self, zoom: f64, x: f64, y: f64, size: PhysicalSize<u32>) {
+        let canvas = self.canvas;
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        if zoom == self.zoom {
+            return;
+        }
+        let old = self.options.canvas;
+        let new = Canvas::new(
+            ((canvas.width as f64 * zoom).round() as usize).max(1),
+            ((canvas.height as f64 * zoom).round() as usize).max(1),
+        );
+        let scale_x = new.width as f64 / old.width as f64;
+        let scale_y = new.height as f64 / old.height as f64;
+        self.scroll_x = (x - (x - self.scroll_x as f64) * scale_x).round() as i32;
+        self.scroll_y = (y - (y - self.scroll_y as f64) * scale_y).round() as i32;
+        self.zoom = zoom;
+
+        let options = RenderOptions {
+            canvas: new,
+            ..self.options
+        };
+        let rect = self.rect_to_render(new, size);
+        self.render::<Artwork>(options, rect);
+    }
+
+    /// Where the canvas is drawn in a window of `size`
+    fn placement(&self, size: PhysicalSize<u32>) -> Placement {
+        Placement::new(
+            self.fit,
+            self.options.canvas,
+            (size.width as usize, size.height as usize),
+            (self.scroll_x, self.scroll_y),
+        )
+    }
+
+    /// Switches to `fit`. Scaling needs the whole of the starting canvas, so any zoom is reset,
+    /// and the canvas is centred when back at its actual size.
+    fn set_fit<Artwork: Art>(&mut self, fit: Fit, size: PhysicalSize<u32>) {
+        self.fit = fit;
+        if fit == Fit::Actual {
+            self.scroll_x = (size.width as i32 - self.options.canvas.width as i32) / 2;
+            self.scroll_y = (size.height as i32 - self.options.canvas.height as i32) / 2;
+        } else {
+            self.zoom::<Artwork>(1., 0., 0., size);
+        }
+    }
+
+    /// Switches to [`Fit::Actual`] if the image is scaled, scrolling so the canvas point under
+    /// the window point `(x, y)` stays there
+    fn actual_size(&mut self, x: f64, y: f64, size: PhysicalSize<u32>) {
+        if self.fit != Fit::Actual {
+            let (px, py) = self.placement(size).canvas_at(x, y);
+            self.scroll_x = (x - px).round() as i32;
+            self.scroll_y = (y - py).round() as i32;
+            self.fit = Fit::Actual;
+        }
+    }
+
+    /// Moves the viewport to the visible part of the canvas, so it is rendered first,
+    /// and re-renders if scrolling has revealed part of the canvas that is not in `image`
+    fn render_visible<Artwork: Art>(&mut self, size: PhysicalSize<u32>) {
+        let canvas = self.options.canvas;
+        let visible = self.visible(canvas, size, false);
+        if self.viewport.get() != Some(visible) {
+            self.viewport.set(visible);
+        }
+        if !visible.is_empty() && !self.rect.contains(&visible) {
+            let rect = self.rect_to_render(canvas, size);
+            self.render::<Artwork>(self.options, rect);
+        }
+    }
+
+    /// The part of `canvas` to render for the window: all of it unless zoomed in, otherwise
+    /// the visible part plus a margin, so panning a little does not need a re-render
+    fn rect_to_render(&self, canvas: Canvas, size: PhysicalSize<u32>) -> Rect {
+        if self.zoom <= 1. {
+            canvas.rect()
+        } else {
+            self.visible(canvas, size, true)
+        }
+    }
+
+    /// The part of `canvas` visible in the window, plus half a window on each side with `margin`
+    fn visible(&self, canvas: Canvas, size: PhysicalSize<u32>, margin: bool) -> Rect {
+        let (width, height) = (size.width as usize, size.height as usize);
+        let placement = Placement::new(
+            self.fit,
+            canvas,
+            (width, height),
+            (self.scroll_x, self.scroll_y),
+        );
+        let (left, top) = placement.canvas_at(0., 0.);
+        let (right, bottom) = placement.canvas_at(width as f64, height as f64);
+        let span = |start: f64, end: f64, len: usize| {
+            let margin = if margin { (end - start) / 2. } else { 0. };
+            let start = (start - margin).floor().clamp(0., len as f64);
+            let end = (end + margin).ceil().clamp(0., len as f64);
+            (start as usize, (end - start) as usize)
+        };
+        let (x, width) = span(left, right, canvas.width);
+        let (y, height) = span(top, bottom, canvas.height);
+        Rect::new(x, y, width, height)
+    }
+}
+
+pub fn run<Artwork: Art>(options: RenderOptions) {
+    run_many(vec![ViewedArtwork::new::<Artwork>(options)]);
+}
+
+/// Shows each of `artworks` in turn in one window, switching with Tab
+pub fn run_many(artworks: Vec<ViewedArtwork>) {
+    assert!(!artworks.is_empty(), "should have an artwork to view");
+    let event_loop = EventLoop::new().unwrap();
+    let mut artworks = Some(artworks);
+
+    let app = winit_app::WinitAppBuilder::with_init(move |elwt| {
+        let artworks = artworks.take().expect("the viewer should start once");
         State {
-            pane: Pane::new(elwt),
+            pane: Pane::new(elwt, artworks[0].title),
             key_modifiers: Default::default(),
-            image,
-            rx,
             last_tick: Instant::now(),
-            time_started: Instant::now(),
-            finished: false,
-            drawn: 0,
+            hud: true,
+            channel: Channel::All,
+            shown: Shown::Image,
             mouse: Default::default(),
-            scroll_x: 0,
-            scroll_y: 0,
+            artworks,
+            current: 0,
         }
     })
-    .with_event_handler(|state, event, elwt| {
-        elwt.set_control_flow(ControlFlow::Poll);
-
-        let State {
-            pane: Pane { window, surface },
-            key_modifiers,
-            image,
-            rx,
-            last_tick,
-            time_started,
-            finished,
-            drawn,
-            mouse,
-            scroll_x,
-            scroll_y,
-        } = state;
-
-        let image_len = image.len();
-
-        while let Ok(PixelReady { index, pixels }) = rx.try_recv() {
-            let len = (index + BATCH_SIZE).min(image_len) - index;
-            image[index..(index + len)].copy_from_slice(&pixels);
-            *drawn += len;
+    .with_event_handler(move |state, event, elwt| {
+        // the artworks not shown are paused, but may still have batches on their way
+        for (i, artwork) in state.artworks.iter_mut().enumerate() {
+            if i != state.current && artwork.receive() {
+                let elapsed = artwork.finished_in.unwrap_or_default();
+                println!("{title} finished in {elapsed:?}", title = artwork.title);
+            }
         }
+        let handle = state.artworks[state.current].handle;
+        handle(state, event, elwt);
+    });
+    winit_app::run_app(event_loop, app);
+}
 
-        if !*finished && *drawn >= image_len {
-            *finished = true;
-            println!("Finished in {elapsed:?}", elapsed = time_started.elapsed());
+/// Handles `event` for the artwork shown, which is an `Artwork`
+fn handle<Artwork: Art>(state: &mut State, event: Event<()>, elwt: &ActiveEventLoop) {
+    elwt.set_control_flow(ControlFlow::Poll);
+
+    let mut zoom = None;
+    let mut new_fit = None;
+    let mut switch = None;
+    let mut close = false;
+    let size = state.pane.window.inner_size();
+    let artwork = &mut state.artworks[state.current];
+    if artwork.job.is_none() {
+        artwork.render::<Artwork>(artwork.options, artwork.rect);
+        artwork.render_visible::<Artwork>(size);
+    }
+    let placement = artwork.placement(size);
+
+    let State {
+        pane: Pane { window, surface },
+        key_modifiers,
+        last_tick,
+        hud,
+        channel,
+        shown,
+        mouse,
+        artworks,
+        current,
+    } = state;
+    let artwork = &mut artworks[*current];
+    let just_finished = artwork.receive();
+    let ViewedArtwork {
+        options,
+        zoom: current_zoom,
+        rect,
+        image,
+        times,
+        time_map,
+        fit,
+        scaled,
+        finished_in,
+        progress,
+        trace_tx,
+        trace_rx,
+        trace_panel,
+        hover,
+        loupe,
+        reference,
+        scroll_x,
+        scroll_y,
+        ..
+    } = artwork;
+
+    if let Some(panel) = trace_rx.try_iter().last() {
+        *trace_panel = Some(panel);
+    }
+
+    let pixel = mouse.pixel(options.canvas, placement);
+    // the trace is only shown in the HUD
+    hover.cursor(
+        pixel
+            .filter(|_| *hud)
+            .map(|(x, y)| options.canvas.mn::<Artwork>(x as f64 + 0.5, y as f64 + 0.5)),
+    );
+    hover.update::<Artwork>();
+    loupe.update::<Artwork>(options.canvas, pixel);
+
+    let comparing = *shown == Shown::Differences || just_finished;
+    if let Some(reference) = reference.as_mut().filter(|_| comparing) {
+        if reference.update(image, *rect, options.canvas, finished_in.is_none()) {
+            scaled.stale = true;
         }
+    }
+    if *shown == Shown::Times && time_map.update(times, *rect, finished_in.is_none()) {
+        scaled.stale = true;
+    }
 
-        let refresh_rate = window
-            .current_monitor()
-            .and_then(|mon| mon.refresh_rate_millihertz())
-            .unwrap_or(60000);
-        if last_tick.elapsed() >= Duration::from_secs_f64(1000. / refresh_rate as f64) {
-            window.request_redraw();
-            *last_tick = Instant::now();
+    if let Some(elapsed) = finished_in.filter(|_| just_finished) {
+        window.set_title(Artwork::TITLE);
+        println!("Finished in {elapsed:?}");
+        if let Some(Ok((diff, _))) = reference.as_ref().map(Reference::diff) {
+            print!("Compared with the reference: ");
+            diff.print(LISTED_DIFFERENCES);
+        }
+    } else if finished_in.is_none() {
+        if let Some(progress) = progress.report(PROGRESS_INTERVAL) {
+            window.set_title(&format!(
+                "{title} - {percent:.1}%, ETA {eta}",
+                title = Artwork::TITLE,
+                percent = progress.fraction() * 100.,
+                eta = progress
+                    .eta()
+                    .map_or("-".to_string(), |eta| FormatDuration(eta).to_string()),
+            ));
+            println!("{progress}");
         }
+    }
 
-        match event {
-            Event::WindowEvent { window_id, event } if window_id == window.id() => match event {
-                WindowEvent::Resized(size) => {
-                    if let (Some(x_len), Some(y_len)) =
-                        (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
-                    {
-                        surface.resize(x_len, y_len).unwrap();
-                    }
-                }
-                WindowEvent::ModifiersChanged(modifiers) => {
-                    *key_modifiers = modifiers.state();
+    let refresh_rate = window
+        .current_monitor()
+        .and_then(|mon| mon.refresh_rate_millihertz())
+        .unwrap_or(60000);
+    if last_tick.elapsed() >= Duration::from_secs_f64(1000. / refresh_rate as f64) {
+        window.request_redraw();
+        *last_tick = Instant::now();
+    }
+
+    match event {
+        Event::WindowEvent { window_id, event } if window_id == window.id() => match event {
+            WindowEvent::Resized(size) => {
+                if let (Some(x_len), Some(y_len)) =
+                    (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+                {
+                    surface.resize(x_len, y_len).unwrap();
                 }
-                WindowEvent::KeyboardInput { event, .. } => {
-                    let KeyEvent {
-                        logical_key, state, ..
-                    } = event;
-
-                    if state.is_pressed() {
-                        match logical_key {
-                            Key::Named(NamedKey::ArrowLeft) => {
-                                *scroll_x -= 20;
-                            }
-                            Key::Named(NamedKey::ArrowRight) => {
-                                *scroll_x += 20;
-                            }
-                            Key::Named(NamedKey::ArrowUp) => {
-                                *scroll_y -= 20;
-                            }
-                            Key::Named(NamedKey::ArrowDown) => {
-                                *scroll_y += 20;
-                            }
-                            Key::Named(NamedKey::Escape) => {
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                *key_modifiers = modifiers.state();
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let KeyEvent {
+                    logical_key, state, ..
+                } = event;
+
+                let used_by_panel = state.is_pressed()
+                    && trace_panel
+                        .as_mut()
+                        .is_some_and(|panel| panel.key(&logical_key));
+                if state.is_pressed() && !used_by_panel {
+                    match logical_key {
+                        Key::Named(NamedKey::ArrowLeft) => {
+                            *scroll_x -= 20;
+                        }
+                        Key::Named(NamedKey::ArrowRight) => {
+                            *scroll_x += 20;
+                        }
+                        Key::Named(NamedKey::ArrowUp) => {
+                            *scroll_y -= 20;
+                        }
+                        Key::Named(NamedKey::ArrowDown) => {
+                            *scroll_y += 20;
+                        }
+                        Key::Named(NamedKey::Escape) => {
+                            if trace_panel.take().is_none() {
                                 *scroll_x = 0;
                                 *scroll_y = 0;
                             }
-                            Key::Named(NamedKey::Enter) => {}
-                            Key::Named(NamedKey::Space) => {}
-                            Key::Named(key) => {
-                                if let Some(text) = key.to_text() {
-                                    for _c in text.chars() {}
-                                }
+                        }
+                        Key::Named(NamedKey::Tab) => {
+                            switch = Some(if key_modifiers.shift_key() { -1 } else { 1 });
+                        }
+                        Key::Named(NamedKey::Enter) => {}
+                        Key::Named(NamedKey::Space) => {}
+                        Key::Named(key) => {
+                            if let Some(text) = key.to_text() {
+                                for _c in text.chars() {}
                             }
-                            Key::Character(text) => {
-                                if !key_modifiers.control_key() {
-                                    for _c in text.chars() {}
+                        }
+                        Key::Character(text) if !key_modifiers.control_key() => {
+                            let size = window.inner_size();
+                            let (centre_x, centre_y) =
+                                (size.width as f64 / 2., size.height as f64 / 2.);
+                            for c in text.chars() {
+                                match c {
+                                    's' => save_screenshot::<Artwork>(*rect, image.clone()),
+                                    'h' => *hud = !*hud,
+                                    'c' => *channel = channel.next(),
+                                    'f' => new_fit = Some(fit.next()),
+                                    't' => hover.toggle(),
+                                    'l' => loupe.cycle(),
+                                    'd' => *shown = shown.toggle(Shown::Differences),
+                                    'p' => *shown = shown.toggle(Shown::Times),
+                                    '+' | '=' => {
+                                        zoom = Some(Zoom::By {
+                                            factor: ZOOM_STEP,
+                                            x: centre_x,
+                                            y: centre_y,
+                                        })
+                                    }
+                                    '-' => {
+                                        zoom = Some(Zoom::By {
+                                            factor: 1. / ZOOM_STEP,
+                                            x: centre_x,
+                                            y: centre_y,
+                                        })
+                                    }
+                                    '0' => zoom = Some(Zoom::Reset),
+                                    _ => {}
                                 }
                             }
-                            _ => {}
                         }
-                    };
-                }
-                WindowEvent::CursorMoved {
-                    device_id: _,
-                    position,
-                } => {
-                    mouse.prev_x = mouse.x;
-                    mouse.prev_y = mouse.y;
-
-                    mouse.x = position.x;
-                    mouse.y = position.y;
-
-                    let dx = mouse.x - mouse.prev_x;
-                    let dy = mouse.y - mouse.prev_y;
-
-                    if mouse.middle_state == ElementState::Pressed {
-                        *scroll_x += dx as i32;
-                        *scroll_y += dy as i32;
+                        _ => {}
                     }
+                };
+            }
+            WindowEvent::CursorMoved {
+                device_id: _,
+                position,
+            } => {
+                mouse.prev_x = mouse.x;
+                mouse.prev_y = mouse.y;
+
+                mouse.x = position.x;
+                mouse.y = position.y;
+
+                let dx = mouse.x - mouse.prev_x;
+                let dy = mouse.y - mouse.prev_y;
+
+                if mouse.middle_state == ElementState::Pressed {
+                    *scroll_x += dx as i32;
+                    *scroll_y += dy as i32;
                 }
-                WindowEvent::MouseInput {
-                    device_id: _,
-                    state,
-                    button,
-                } => {
-                    if button == MouseButton::Left {
-                        let x = mouse.x as isize - *scroll_x as isize;
-                        let y = mouse.y as isize - *scroll_y as isize;
-                        if state == ElementState::Released
-                            && x >= 0
-                            && y >= 0
-                            && x < Artwork::FULL_M as isize
-                            && y < Artwork::FULL_N as isize
-                        {
-                            struct Temp {
-                                lines: Vec<(usize, String)>,
-                                callstack: Vec<usize>,
-                            }
-
-                            impl Temp {
-                                fn current(&mut self) -> &'_ mut String {
-                                    &mut self.lines[*self.callstack.last().unwrap()].1
-                                }
-                            }
-
-                            let mut tmp = Temp {
-                                lines: Vec::new(),
-                                callstack: Vec::new(),
-                            };
-
-                            std::thread::spawn(move || {
-                                set_should_track(true);
-
-                                let m = (x + 1) as f64;
-                                let n = (y + 1) as f64;
-                                let rgb = Artwork::draw(m, n);
-                                println!("@ m = {m}, n = {n}");
-
-                                set_should_track(false);
-                                with_stack(|stack| -> Result<(), std::fmt::Error> {
-                                    tmp.lines.reserve(stack.len() / 4); // Minimum items is: Start, Arg* (since always >= 1 arg), ArgEnd, Finish*
-                                    tmp.callstack.reserve(stack.len() / 4); // Minimum items is: Start, Arg* (since always >= 1 arg), ArgEnd, Finish*
-                                    let mut depth = 0usize;
-                                    for item in stack.drain(..) {
-                                        match item {
-                                            track::Item::Start(name) => {
-                                                tmp.lines.push((depth, "  ".repeat(depth)));
-                                                tmp.callstack.push(tmp.lines.len() - 1);
-                                                write!(tmp.current(), "{name}(")?;
-                                                depth += 1;
-                                            }
-                                            track::Item::ArgUsize(arg, val) => {
-                                                write!(tmp.current(), "{arg} = {val}, ")?;
-                                            }
-                                            track::Item::ArgF64(arg, val) => {
-                                                write!(tmp.current(), "{arg} = {val:.3}, ")?;
-                                            }
-                                            track::Item::ArgEnd => {
-                                                tmp.current().pop(); // remove extra " "
-                                                tmp.current().pop(); // remove extra ","
-                                                write!(tmp.current(), ")")?;
-                                            }
-                                            track::Item::FinishRgb(r, g, b) => {
-                                                write!(tmp.current(), " = ({r}, {g}, {b})")?;
-                                                tmp.callstack.pop();
-                                                depth -= 1;
-                                            }
-                                            track::Item::FinishF64(output) => {
-                                                write!(tmp.current(), " = {output:.3}")?;
-                                                tmp.callstack.pop();
-                                                depth -= 1;
-                                            }
-                                        }
-                                    }
-                                    for (_, line) in tmp.lines.drain(..) {
-                                        println!("  {line}");
-                                    }
-                                    Ok(())
-                                })
-                                .expect("Writing to a string should have succeeded");
-
-                                println!("=> rgb({}, {}, {})", rgb.0, rgb.1, rgb.2);
-                            });
-                        }
-
-                        mouse.left_state = state;
-                    } else if button == MouseButton::Middle {
-                        mouse.middle_state = state;
-                    } else if button == MouseButton::Right && state == ElementState::Pressed {
-                        *scroll_x = 0;
-                        *scroll_y = 0;
+            }
+            WindowEvent::MouseInput {
+                device_id: _,
+                state,
+                button,
+            } => {
+                if button == MouseButton::Left {
+                    let canvas = options.canvas;
+                    let over_panel = trace_panel.is_some()
+                        && mouse.x >= TracePanel::left(window.inner_size().width as usize) as f64;
+                    if let (ElementState::Released, false, Some((x, y))) =
+                        (state, over_panel, mouse.pixel(canvas, placement))
+                    {
+                        let trace_tx = trace_tx.clone();
+                        std::thread::spawn(move || {
+                            let (m, n) = canvas.mn::<Artwork>(x as f64 + 0.5, y as f64 + 0.5);
+                            let panel = TracePanel::new(trace::<Artwork>(m, n), canvas);
+                            // the viewer may have closed in the meantime
+                            let _ = trace_tx.send(panel);
+                        });
                     }
+
+                    mouse.left_state = state;
+                } else if button == MouseButton::Middle {
+                    mouse.middle_state = state;
+                } else if button == MouseButton::Right && state == ElementState::Pressed {
+                    *scroll_x = 0;
+                    *scroll_y = 0;
                 }
-                WindowEvent::MouseWheel {
-                    device_id: _,
-                    delta,
-                    phase: _,
-                } => {
+            }
+            WindowEvent::MouseWheel {
+                device_id: _,
+                delta,
+                phase: _,
+            } => {
+                let over_panel = trace_panel.is_some()
+                    && mouse.x >= TracePanel::left(window.inner_size().width as usize) as f64;
+                if let Some(panel) = trace_panel.as_mut().filter(|_| over_panel) {
+                    let steps = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y as f64,
+                        MouseScrollDelta::PixelDelta(pos) => pos.y / 20.,
+                    };
+                    panel.move_by(-steps.round() as isize * trace_panel::SCROLL_STEP);
+                } else if key_modifiers.control_key() {
+                    let steps = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y as f64,
+                        MouseScrollDelta::PixelDelta(pos) => pos.y / 100.,
+                    };
+                    zoom = Some(Zoom::By {
+                        factor: ZOOM_STEP.powf(steps),
+                        x: mouse.x,
+                        y: mouse.y,
+                    });
+                } else {
                     let (offset_x, offset_y) = match delta {
-                        winit::event::MouseScrollDelta::LineDelta(x, y) => {
-                            (x as i32 * 100, y as i32 * 100)
-                        }
-                        winit::event::MouseScrollDelta::PixelDelta(pos) => {
-                            (pos.x as i32, pos.y as i32)
-                        }
+                        MouseScrollDelta::LineDelta(x, y) => (x as i32 * 100, y as i32 * 100),
+                        MouseScrollDelta::PixelDelta(pos) => (pos.x as i32, pos.y as i32),
                     };
                     *scroll_x += if key_modifiers.shift_key() {
                         offset_y
@@ -345,33 +787,52 @@ pub fn run<Artwork: Art>() {
                         offset_y
                     };
                 }
-                WindowEvent::RedrawRequested => {
-                    let width = surface.window().inner_size().width as isize;
-                    let height = surface.window().inner_size().height as isize;
-                    let mut surface_buffer = surface.buffer_mut().unwrap();
-
-                    if width > 0 && height > 0 {
-                        let start_x = *scroll_x as isize;
+            }
+            WindowEvent::RedrawRequested => {
+                let width = surface.window().inner_size().width as isize;
+                let height = surface.window().inner_size().height as isize;
+                let mut surface_buffer = surface.buffer_mut().unwrap();
+
+                if width > 0 && height > 0 {
+                    let placement = Placement::new(
+                        *fit,
+                        options.canvas,
+                        (width as usize, height as usize),
+                        (*scroll_x, *scroll_y),
+                    );
+                    let replacement = match *shown {
+                        Shown::Image => None,
+                        Shown::Differences => reference
+                            .as_ref()
+                            .and_then(|reference| reference.diff().ok())
+                            .map(|(_, pixels)| pixels),
+                        Shown::Times => Some(time_map.pixels()),
+                    }
+                    .filter(|pixels| pixels.len() == image.len());
+                    let drawn = replacement.map_or(Shown::Image, |_| *shown);
+                    let pixels = replacement.unwrap_or(image);
+                    let dest_buffer = surface_buffer.as_mut();
+                    if *fit == Fit::Actual {
+                        let start_x = *scroll_x as isize + rect.x as isize;
                         let skip_dest_x = start_x.max(0).min(width) as usize;
-                        let skip_src_x = (-start_x).max(0).min(Artwork::FULL_M as isize) as usize;
+                        let skip_src_x = (-start_x).max(0).min(rect.width as isize) as usize;
                         let take_x = (width - skip_dest_x as isize)
-                            .min(Artwork::FULL_M as isize - skip_src_x as isize)
+                            .min(rect.width as isize - skip_src_x as isize)
                             .max(0) as usize;
 
-                        let start_y = *scroll_y as isize;
+                        let start_y = *scroll_y as isize + rect.y as isize;
                         let skip_dest_y = start_y.max(0).min(height) as usize;
-                        let skip_src_y = (-start_y).max(0).min(Artwork::FULL_N as isize) as usize;
+                        let skip_src_y = (-start_y).max(0).min(rect.height as isize) as usize;
                         let take_y = (height - skip_dest_y as isize)
-                            .min(Artwork::FULL_N as isize - skip_src_y as isize)
+                            .min(rect.height as isize - skip_src_y as isize)
                             .max(0) as usize;
 
-                        let src_buffer = image.as_slice();
+                        let src_buffer = pixels;
                         let src_lines = src_buffer
-                            .chunks(Artwork::FULL_M)
+                            .chunks(rect.width.max(1))
                             .skip(skip_src_y)
                             .map(|line| &line[skip_src_x..(skip_src_x + take_x)])
                             .take(take_y);
-                        let dest_buffer = surface_buffer.as_mut();
                         dest_buffer.fill(0);
                         let dest_lines = dest_buffer
                             .chunks_mut(width as usize)
@@ -379,19 +840,162 @@ pub fn run<Artwork: Art>() {
                             .skip(skip_dest_y)
                             .take(take_y);
                         for (src, dest) in src_lines.zip(dest_lines) {
-                            dest.copy_from_slice(src);
+                            if *channel == Channel::All {
+                                dest.copy_from_slice(src);
+                            } else {
+                                for (dest, &src) in dest.iter_mut().zip(src) {
+                                    *dest = channel.apply(src);
+                                }
+                            }
+                        }
+                    } else {
+                        let size = (width as usize, height as usize);
+                        scaled.update(pixels, *rect, placement, size, drawn);
+                        for (dest, &src) in dest_buffer.iter_mut().zip(&scaled.pixels) {
+                            *dest = channel.apply(src);
                         }
                     }
 
-                    surface_buffer.present().unwrap();
-                }
-                WindowEvent::CloseRequested => {
-                    elwt.exit();
+                    let mut frame = Frame::new(dest_buffer, width as usize, height as usize);
+                    let scale = overlay_scale(window);
+                    let canvas = options.canvas;
+                    let pixel = mouse.pixel(canvas, placement);
+                    if let Some(pixel) = pixel {
+                        let source = loupe::Source {
+                            canvas,
+                            pixel,
+                            image: pixels,
+                            rect: *rect,
+                            channel: *channel,
+                        };
+                        let cursor = (mouse.x.floor() as isize, mouse.y.floor() as isize);
+                        loupe.draw(&mut frame, scale, cursor, &source);
+                    }
+                    if let Some(panel) = trace_panel {
+                        let Trace { m, n, .. } = panel.trace;
+                        let (px, py) = canvas.point::<Artwork>(m, n);
+                        let size = canvas.width as f64 / panel.canvas.width as f64;
+                        let (x, y) = placement.window_at(px - size / 2., py - size / 2.);
+                        trace_panel::highlight(
+                            &mut frame,
+                            x.floor() as isize,
+                            y.floor() as isize,
+                            (size * placement.scale).ceil().max(1.) as usize,
+                        );
+                        panel.draw(&mut frame, scale);
+                    }
+
+                    if *hud {
+                        let cursor = pixel.map(|(x, y)| {
+                            let (m, n) = canvas.mn::<Artwork>(x as f64 + 0.5, y as f64 + 0.5);
+                            let rgb = rect
+                                .contains(&Rect::new(x, y, 1, 1))
+                                .then(|| image[(y - rect.y) * rect.width + x - rect.x])
+                                .filter(|&pixel| pixel != UNRENDERED)
+                                .map(rgb_from_softbuffer_color);
+                            hud::Cursor { m, n, rgb }
+                        });
+                        let status = hud::Status {
+                            zoom: *current_zoom * placement.scale,
+                            fit: *fit,
+                            channel: *channel,
+                            progress: progress.progress(),
+                            finished_in: *finished_in,
+                            cursor,
+                            hover: hover.shown(),
+                            diff: (*shown == Shown::Differences).then(|| match reference {
+                                Some(reference) => reference.diff().map(|(diff, _)| diff),
+                                None => Err("no reference, see --reference"),
+                            }),
+                            times: (*shown == Shown::Times).then(|| time_map.range()),
+                        };
+                        hud::draw::<Artwork>(&mut frame, scale, &status);
+                    }
                 }
-                _ => {}
-            },
+
+                surface_buffer.present().unwrap();
+            }
+            WindowEvent::CloseRequested => {
+                close = true;
+            }
             _ => {}
+        },
+        _ => {}
+    }
+
+    if close {
+        // rather than rendering until the process exits
+        for artwork in artworks.iter() {
+            if let Some(job) = &artwork.job {
+                job.cancel();
+            }
+        }
+        elwt.exit();
+        return;
+    }
+    if let Some(fit) = new_fit {
+        artwork.set_fit::<Artwork>(fit, size);
+    }
+    if *shown == Shown::Times {
+        artwork.time_pixels::<Artwork>();
+    }
+    match zoom {
+        Some(Zoom::By { factor, x, y }) => {
+            let scale = artwork.placement(size).scale;
+            artwork.actual_size(x, y, size);
+            artwork.zoom::<Artwork>(artwork.zoom * scale * factor, x, y, size)
+        }
+        Some(Zoom::Reset) => {
+            artwork.fit = Fit::Actual;
+            artwork.scroll_x = 0;
+            artwork.scroll_y = 0;
+            artwork.zoom::<Artwork>(1., 0., 0., size);
+        }
+        None => {}
+    }
+    artwork.render_visible::<Artwork>(size);
+
+    if let Some(step) = switch {
+        artworks[*current].pause();
+        *current = (*current as isize + step).rem_euclid(artworks.len() as isize) as usize;
+        let artwork = &artworks[*current];
+        artwork.resume();
+        let title = match artwork.finished_in {
+            Some(_) => artwork.title.to_string(),
+            None => format!("{title} - rendering", title = artwork.title),
+        };
+        window.set_title(&title);
+    }
+}
+
+/// Size of each font pixel of the overlays, larger on high density displays
+fn overlay_scale(window: &Window) -> usize {
+    (window.scale_factor() * 2.).round().max(1.) as usize
+}
+
+/// Saves the rendered part of the canvas, including any unrendered pixels, to the working
+/// directory. After zooming in that is only `rect`, the part re-rendered at the new scale, as the
+/// whole zoomed canvas may be far too large to render.
+/// Encoding happens on another thread so the viewer stays responsive.
+fn save_screenshot<Artwork: Art>(rect: Rect, image: Vec<u32>) {
+    std::thread::spawn(move || {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = PathBuf::from(format!("{name}-{timestamp}.png", name = Artwork::NAME));
+        let unrendered = image.iter().filter(|&&pixel| pixel == UNRENDERED).count();
+        let result = Image::from_softbuffer(rect.width, rect.height, &image).save(&path);
+        match result {
+            Ok(()) if unrendered > 0 => println!(
+                "Saved screenshot to {path} ({unrendered} unrendered pixels marked in magenta)",
+                path = path.display()
+            ),
+            Ok(()) => println!("Saved screenshot to {path}", path = path.display()),
+            Err(err) => eprintln!(
+                "failed to save screenshot to {path}: {err}",
+                path = path.display()
+            ),
         }
     });
-    winit_app::run_app(event_loop, app);
 }
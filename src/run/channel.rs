@@ -0,0 +1,64 @@
+//! Views of one channel of the image as grayscale, cycled with `c`
+
+use std::fmt;
+
+use super::srgb;
+use crate::{render::UNRENDERED, rgb_from_softbuffer_color, softbuffer_color};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Channel {
+    /// Full colour
+    #[default]
+    All,
+    Red,
+    Green,
+    Blue,
+    /// Relative luminance, weighted as in Rec. 709 and sRGB
+    Luminance,
+}
+
+impl Channel {
+    pub fn next(self) -> Self {
+        match self {
+            Channel::All => Channel::Red,
+            Channel::Red => Channel::Green,
+            Channel::Green => Channel::Blue,
+            Channel::Blue => Channel::Luminance,
+            Channel::Luminance => Channel::All,
+        }
+    }
+
+    /// `pixel` in [`softbuffer_color`] format as this channel shows it,
+    /// leaving [`UNRENDERED`] pixels as they are
+    pub fn apply(self, pixel: u32) -> u32 {
+        if pixel == UNRENDERED {
+            return pixel;
+        }
+        let (r, g, b) = rgb_from_softbuffer_color(pixel);
+        let gray = match self {
+            Channel::All => return pixel,
+            Channel::Red => r,
+            Channel::Green => g,
+            Channel::Blue => b,
+            Channel::Luminance => {
+                let y =
+                    0.2126 * srgb::decode(r) + 0.7152 * srgb::decode(g) + 0.0722 * srgb::decode(b);
+                srgb::encode(y)
+            }
+        };
+        softbuffer_color((gray, gray, gray))
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Channel::All => "rgb",
+            Channel::Red => "red",
+            Channel::Green => "green",
+            Channel::Blue => "blue",
+            Channel::Luminance => "luminance",
+        };
+        write!(f, "{name}")
+    }
+}
@@ -0,0 +1,203 @@
+//! Scaling the image to the window, cycled with `f`.
+//! Window sizes are in physical pixels, so on high density displays the image is scaled to the
+//! full resolution of the screen rather than to its logical size.
+
+use std::fmt;
+
+use super::srgb;
+use crate::{render::UNRENDERED, rgb_from_softbuffer_color, softbuffer_color, Canvas, Rect};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fit {
+    /// One canvas pixel per window pixel, scrolled and zoomed by re-rendering
+    #[default]
+    Actual,
+    /// The whole canvas, as large as fits in the window
+    Contain,
+    /// The canvas covering the whole window, cropped on the sides that overflow
+    Cover,
+    /// The whole canvas, shrunk by the smallest whole factor that fits, so each window pixel
+    /// is the average of a square of canvas pixels
+    Integer,
+}
+
+impl Fit {
+    pub fn next(self) -> Self {
+        match self {
+            Fit::Actual => Fit::Contain,
+            Fit::Contain => Fit::Cover,
+            Fit::Cover => Fit::Integer,
+            Fit::Integer => Fit::Actual,
+        }
+    }
+}
+
+impl fmt::Display for Fit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Fit::Actual => "actual size",
+            Fit::Contain => "fit",
+            Fit::Cover => "fill",
+            Fit::Integer => "integer",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Where the canvas is drawn in the window: the canvas point `(px, py)` is at the window point
+/// `(px * scale + x, py * scale + y)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub scale: f64,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Placement {
+    /// Places `canvas` in a window `width` x `height` as `fit` says,
+    /// at `(scroll_x, scroll_y)` for [`Fit::Actual`] and centred otherwise
+    pub fn new(
+        fit: Fit,
+        canvas: Canvas,
+        (width, height): (usize, usize),
+        scroll: (i32, i32),
+    ) -> Self {
+        let scale_x = width as f64 / canvas.width as f64;
+        let scale_y = height as f64 / canvas.height as f64;
+        let scale = match fit {
+            Fit::Actual => {
+                return Self {
+                    scale: 1.,
+                    x: scroll.0 as f64,
+                    y: scroll.1 as f64,
+                }
+            }
+            Fit::Contain => scale_x.min(scale_y),
+            Fit::Cover => scale_x.max(scale_y),
+            Fit::Integer => 1. / (1. / scale_x.min(scale_y)).ceil().max(1.),
+        };
+        Self {
+            scale,
+            x: ((width as f64 - canvas.width as f64 * scale) / 2.).round(),
+            y: ((height as f64 - canvas.height as f64 * scale) / 2.).round(),
+        }
+    }
+
+    /// The canvas point at the window point `(x, y)`
+    pub fn canvas_at(&self, x: f64, y: f64) -> (f64, f64) {
+        ((x - self.x) / self.scale, (y - self.y) / self.scale)
+    }
+
+    /// The window point at the canvas point `(px, py)`
+    pub fn window_at(&self, px: f64, py: f64) -> (f64, f64) {
+        (px * self.scale + self.x, py * self.scale + self.y)
+    }
+}
+
+/// Canvas pixels making up one window pixel along an axis, with the weight of each
+struct Span {
+    start: usize,
+    weights: Vec<f64>,
+}
+
+/// The canvas pixels making up each of the `len` window pixels along an axis, where the canvas,
+/// `canvas_len` pixels long, starts at `offset` and is scaled by `scale`.
+/// Shrinking, these are the canvas pixels the window pixel covers, weighted by how much of it
+/// each covers. Enlarging, they are the two canvas pixels nearest its centre, weighted for
+/// linear interpolation, so the image is smooth rather than blocky.
+/// `None` for window pixels off the canvas.
+fn spans(len: usize, canvas_len: usize, offset: f64, scale: f64) -> Vec<Option<Span>> {
+    (0..len)
+        .map(|i| {
+            let start = ((i as f64 - offset) / scale).max(0.);
+            let end = ((i as f64 + 1. - offset) / scale).min(canvas_len as f64);
+            if start >= end {
+                return None;
+            }
+            if scale > 1. {
+                // between the centres of the canvas pixels, clamped to the edge ones
+                let centre = (i as f64 + 0.5 - offset) / scale - 0.5;
+                let last = canvas_len - 1;
+                let first = (centre.floor().max(0.) as usize).min(last);
+                let fraction = (centre - first as f64).clamp(0., 1.);
+                let weights = if first == last {
+                    vec![1.]
+                } else {
+                    vec![1. - fraction, fraction]
+                };
+                return Some(Span {
+                    start: first,
+                    weights,
+                });
+            }
+            let first = start.floor() as usize;
+            let weights = (first..end.ceil() as usize)
+                .map(|pixel| end.min(pixel as f64 + 1.) - start.max(pixel as f64))
+                .collect();
+            Some(Span {
+                start: first,
+                weights,
+            })
+        })
+        .collect()
+}
+
+/// Draws `image`, which holds `rect` of the canvas, into `pixels`, a window `width` wide,
+/// as `placement` says. Shrinking, each window pixel is the area-weighted average, in linear
+/// light, of the canvas pixels it covers; enlarging, it is interpolated bilinearly, in linear
+/// light, between the four canvas pixels nearest its centre. Either way, canvas pixels that are
+/// [`UNRENDERED`] are left out.
+/// Window pixels off the canvas are black, and those covering only unrendered pixels are
+/// [`UNRENDERED`].
+pub fn resample(image: &[u32], rect: Rect, placement: Placement, pixels: &mut [u32], width: usize) {
+    let height = pixels.len() / width.max(1);
+    let columns = spans(
+        width,
+        rect.width,
+        placement.x + rect.x as f64 * placement.scale,
+        placement.scale,
+    );
+    let rows = spans(
+        height,
+        rect.height,
+        placement.y + rect.y as f64 * placement.scale,
+        placement.scale,
+    );
+    for (row, pixels) in rows.iter().zip(pixels.chunks_mut(width.max(1))) {
+        let Some(row) = row else {
+            pixels.fill(0);
+            continue;
+        };
+        for (column, pixel) in columns.iter().zip(pixels.iter_mut()) {
+            let Some(column) = column else {
+                *pixel = 0;
+                continue;
+            };
+            let (mut sum, mut total) = ((0., 0., 0.), 0.);
+            for (y, row_weight) in (row.start..).zip(&row.weights) {
+                let line = &image[y * rect.width..];
+                for (x, column_weight) in (column.start..).zip(&column.weights) {
+                    let color = line[x];
+                    if color == UNRENDERED {
+                        continue;
+                    }
+                    let (r, g, b) = rgb_from_softbuffer_color(color);
+                    let weight = row_weight * column_weight;
+                    sum.0 += weight * srgb::decode(r);
+                    sum.1 += weight * srgb::decode(g);
+                    sum.2 += weight * srgb::decode(b);
+                    total += weight;
+                }
+            }
+            *pixel = if total > 0. {
+                softbuffer_color((
+                    srgb::encode(sum.0 / total),
+                    srgb::encode(sum.1 / total),
+                    srgb::encode(sum.2 / total),
+                ))
+            } else {
+                UNRENDERED
+            };
+        }
+    }
+}
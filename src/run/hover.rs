@@ -0,0 +1,91 @@
+//! Continuous tracing of the pixel under the cursor, toggled with `t`
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    trace::{trace, Trace},
+    Art,
+};
+
+/// How long the cursor has to rest on a pixel before it is traced,
+/// so sweeping across the canvas does not start a trace for every pixel passed over
+const DEBOUNCE: Duration = Duration::from_millis(30);
+
+pub struct Hover {
+    pub enabled: bool,
+    /// Trace of the pixel most recently under the cursor
+    pub trace: Option<Trace>,
+    /// `(m, n)` of the pixel under the cursor, if it has not been traced yet,
+    /// and when the cursor reached it
+    wanted: Option<((f64, f64), Instant)>,
+    /// `(m, n)` of the pixel being traced
+    tracing: Option<(f64, f64)>,
+    tx: Sender<Trace>,
+    rx: Receiver<Trace>,
+}
+
+impl Hover {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            enabled: false,
+            trace: None,
+            wanted: None,
+            tracing: None,
+            tx,
+            rx,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.trace = None;
+        self.wanted = None;
+    }
+
+    /// The trace to show, if enabled
+    pub fn shown(&self) -> Option<&Trace> {
+        self.trace.as_ref().filter(|_| self.enabled)
+    }
+
+    /// Notes the pixel now under the cursor, as `(m, n)`, or `None` if there is nothing to
+    /// trace, which drops any trace still waiting for [`DEBOUNCE`]
+    pub fn cursor(&mut self, mn: Option<(f64, f64)>) {
+        let Some(mn) = mn.filter(|_| self.enabled) else {
+            self.wanted = None;
+            return;
+        };
+        let traced = self.trace.as_ref().map(|trace| (trace.m, trace.n));
+        if traced == Some(mn) || self.tracing == Some(mn) {
+            self.wanted = None;
+        } else if self.wanted.map(|(wanted, _)| wanted) != Some(mn) {
+            self.wanted = Some((mn, Instant::now()));
+        }
+    }
+
+    /// Receives finished traces, and starts tracing the pixel under the cursor on another
+    /// thread once [`DEBOUNCE`] has passed and no other trace is being computed
+    pub fn update<Artwork: Art>(&mut self) {
+        if let Some(trace) = self.rx.try_iter().last() {
+            self.tracing = None;
+            if self.enabled {
+                self.trace = Some(trace);
+            }
+        }
+        let Some(((m, n), since)) = self.wanted else {
+            return;
+        };
+        if self.enabled && self.tracing.is_none() && since.elapsed() >= DEBOUNCE {
+            self.wanted = None;
+            self.tracing = Some((m, n));
+            let tx = self.tx.clone();
+            std::thread::spawn(move || {
+                // the viewer may have closed in the meantime
+                let _ = tx.send(trace::<Artwork>(m, n));
+            });
+        }
+    }
+}
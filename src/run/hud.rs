@@ -0,0 +1,153 @@
+//! The viewer's heads-up display, toggled with `h`
+
+use std::time::Duration;
+
+use super::{channel::Channel, fit::Fit};
+use crate::{
+    diff::Diff,
+    heatmap,
+    overlay::Frame,
+    progress::{FormatDuration, Progress},
+    trace::{Call, Trace, Value},
+    Art,
+};
+
+/// Distance of the HUD from the corner of the window, in font pixels
+const MARGIN: isize = 4;
+
+/// What is under the mouse cursor
+pub struct Cursor {
+    /// Native coordinates, see [`Canvas::mn`](crate::Canvas::mn)
+    pub m: f64,
+    pub n: f64,
+    /// `None` until the pixel is rendered
+    pub rgb: Option<(u8, u8, u8)>,
+}
+
+/// Everything the HUD shows
+pub struct Status<'a> {
+    /// Window pixels per pixel of the starting canvas
+    pub zoom: f64,
+    pub fit: Fit,
+    pub channel: Channel,
+    pub progress: Progress,
+    pub finished_in: Option<Duration>,
+    pub cursor: Option<Cursor>,
+    /// Trace of the pixel under the cursor, see [`Hover`](super::hover::Hover)
+    pub hover: Option<&'a Trace>,
+    /// While showing the differences from the reference, them or why there are none to show
+    pub diff: Option<Result<&'a Diff, &'static str>>,
+    /// While showing the time per pixel, the seconds at the ends of its colour ramp,
+    /// once any pixels are rendered
+    pub times: Option<Option<(f64, f64)>>,
+}
+
+/// Draws the HUD in the top-left corner of `frame`
+pub fn draw<Artwork: Art>(frame: &mut Frame, scale: usize, status: &Status) {
+    let Status {
+        zoom,
+        fit,
+        channel,
+        ref progress,
+        finished_in,
+        ref cursor,
+        hover,
+        diff,
+        times,
+    } = *status;
+    let mut lines = vec![match (zoom == 1., fit) {
+        (true, Fit::Actual) => Artwork::TITLE.to_string(),
+        (false, Fit::Actual) => format!("{title} at {zoom:.2}x", title = Artwork::TITLE),
+        (_, fit) => format!("{title} at {zoom:.2}x, {fit}", title = Artwork::TITLE),
+    }];
+    if channel != Channel::All {
+        lines.push(format!("showing {channel}"));
+    }
+    match diff {
+        Some(Ok(diff)) => {
+            lines.push("showing differences from the reference".to_string());
+            lines.push(format!(
+                "{differing} of {compared} px differ",
+                differing = diff.differing.len(),
+                compared = diff.compared
+            ));
+            lines.push(format!(
+                "max error {max}  mean {mean:.4}",
+                max = diff.max_error,
+                mean = diff.mean_error
+            ));
+        }
+        Some(Err(reason)) => lines.push(format!("differences: {reason}")),
+        None => {}
+    }
+    if let Some(range) = times {
+        lines.push("showing time per pixel".to_string());
+        if let Some((min, max)) = range {
+            lines.push(format!(
+                "{min} to {max} per px",
+                min = heatmap::format_seconds(min),
+                max = heatmap::format_seconds(max)
+            ));
+        }
+    }
+    match finished_in {
+        Some(elapsed) => lines.push(format!("rendered in {}", FormatDuration(elapsed))),
+        None => {
+            lines.push(format!(
+                "{percent:.1}%  {rate:.0} px/s",
+                percent = progress.fraction() * 100.,
+                rate = progress.pixels_per_second()
+            ));
+            lines.push(format!(
+                "elapsed {elapsed}  ETA {eta}",
+                elapsed = FormatDuration(progress.elapsed),
+                eta = progress
+                    .eta()
+                    .map_or("-".to_string(), |eta| FormatDuration(eta).to_string())
+            ));
+        }
+    }
+    if let Some(Cursor { m, n, rgb }) = *cursor {
+        let (x, y) = Artwork::xy(m, n);
+        lines.push(format!("m, n  {m:.2}, {n:.2}"));
+        lines.push(format!("x, y  {x:.5}, {y:.5}"));
+        lines.push(match rgb {
+            Some((r, g, b)) => format!("rgb   {r}, {g}, {b}"),
+            None => "rgb   -".to_string(),
+        });
+    }
+
+    if let Some(trace) = hover {
+        lines.push(format!("trace @ {m:.2}, {n:.2}", m = trace.m, n = trace.n));
+        lines.extend(
+            trace
+                .calls
+                .iter()
+                .map(|call| format!("  {}", summary(call))),
+        );
+    }
+
+    let margin = MARGIN * scale as isize;
+    frame.panel(margin, margin, &lines, scale);
+}
+
+/// A top-level call without its `f64` arguments, which are mostly the pixel's `(x, y)`,
+/// e.g. `H(v = 0) = 0.252`
+fn summary(call: &Call) -> String {
+    let args = call
+        .args
+        .iter()
+        .filter(|(_, value)| !matches!(value, Value::F64(_)))
+        .map(|(arg, value)| format!("{arg} = {value}"))
+        .collect::<Vec<_>>();
+    if args.is_empty() {
+        format!("{name} = {result}", name = call.name, result = call.result)
+    } else {
+        format!(
+            "{name}({args}) = {result}",
+            name = call.name,
+            args = args.join(", "),
+            result = call.result
+        )
+    }
+}
@@ -0,0 +1,197 @@
+//! A magnifier over the pixels around the cursor, toggled with `l`
+
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{overlay::Frame, render::UNRENDERED, softbuffer_color, Art, Canvas, Rect};
+
+use super::{channel::Channel, trace_panel};
+
+/// Canvas pixels shown on each side of the one under the cursor
+const RADIUS: usize = 5;
+/// Canvas pixels across the loupe
+const SIDE: usize = 2 * RADIUS + 1;
+/// Window pixels across each canvas pixel in the loupe, before the overlay scale
+const MAGNIFICATION: usize = 16;
+/// Points evaluated across each canvas pixel in [`Mode::Detail`],
+/// which should divide [`MAGNIFICATION`]
+const DETAIL: usize = 8;
+/// Distance from the cursor to the loupe, before the overlay scale
+const GAP: isize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Off,
+    /// The pixels around the cursor, enlarged
+    Pixels,
+    /// The artwork evaluated at [`DETAIL`] points across each pixel
+    Detail,
+}
+
+/// Sub-pixel samples of the pixels around `centre`
+struct Detail {
+    canvas: Canvas,
+    centre: (usize, usize),
+    /// `SIDE * DETAIL` square, [`UNRENDERED`] outside the canvas
+    pixels: Vec<u32>,
+}
+
+/// The rendered image around the cursor, as the loupe shows it
+pub struct Source<'a> {
+    pub canvas: Canvas,
+    /// The pixel of `canvas` under the cursor
+    pub pixel: (usize, usize),
+    /// Holds `rect` of `canvas`
+    pub image: &'a [u32],
+    pub rect: Rect,
+    pub channel: Channel,
+}
+
+pub struct Loupe {
+    mode: Mode,
+    detail: Option<Detail>,
+    /// The canvas and centre of the detail being computed
+    computing: Option<(Canvas, (usize, usize))>,
+    tx: Sender<Detail>,
+    rx: Receiver<Detail>,
+}
+
+impl Loupe {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            mode: Mode::Off,
+            detail: None,
+            computing: None,
+            tx,
+            rx,
+        }
+    }
+
+    /// Cycles from off, to enlarged pixels, to sub-pixel detail, and back to off
+    pub fn cycle(&mut self) {
+        self.mode = match self.mode {
+            Mode::Off => Mode::Pixels,
+            Mode::Pixels => Mode::Detail,
+            Mode::Detail => Mode::Off,
+        };
+    }
+
+    /// Receives finished detail, and in [`Mode::Detail`] starts evaluating the pixels around
+    /// `pixel` of `canvas` in the background, unless that has been done or another is underway
+    pub fn update<Artwork: Art>(&mut self, canvas: Canvas, pixel: Option<(usize, usize)>) {
+        if let Some(detail) = self.rx.try_iter().last() {
+            self.computing = None;
+            self.detail = Some(detail);
+        }
+        let Some(centre) = pixel.filter(|_| self.mode == Mode::Detail) else {
+            return;
+        };
+        let done = self
+            .detail
+            .as_ref()
+            .is_some_and(|detail| (detail.canvas, detail.centre) == (canvas, centre));
+        if done || self.computing.is_some() {
+            return;
+        }
+        self.computing = Some((canvas, centre));
+        let tx = self.tx.clone();
+        std::thread::spawn(move || {
+            let side = SIDE * DETAIL;
+            // across the rayon thread pool like a render, as there are thousands of samples
+            let pixels = (0..side * side)
+                .into_par_iter()
+                .map(|i| {
+                    let offset = |i: usize| (i as f64 + 0.5) / DETAIL as f64 - RADIUS as f64;
+                    let x = centre.0 as f64 + offset(i % side);
+                    let y = centre.1 as f64 + offset(i / side);
+                    if x < 0. || y < 0. || x >= canvas.width as f64 || y >= canvas.height as f64 {
+                        return UNRENDERED;
+                    }
+                    let (m, n) = canvas.mn::<Artwork>(x, y);
+                    softbuffer_color(Artwork::draw(m, n))
+                })
+                .collect();
+            // the viewer may have closed in the meantime
+            let _ = tx.send(Detail {
+                canvas,
+                centre,
+                pixels,
+            });
+        });
+    }
+
+    /// Draws the loupe beside the cursor, at the window point `(x, y)`, showing the pixels of
+    /// `source` around the one under it. In [`Mode::Detail`], shows the pixels enlarged until
+    /// the detail for that pixel is ready.
+    pub fn draw(&self, frame: &mut Frame, scale: usize, (x, y): (isize, isize), source: &Source) {
+        if self.mode == Mode::Off {
+            return;
+        }
+        let Source {
+            canvas,
+            pixel,
+            image,
+            rect,
+            channel,
+        } = *source;
+        let enlarged = (0..SIDE * SIDE)
+            .map(|i| {
+                let source_x = (pixel.0 + i % SIDE).checked_sub(RADIUS);
+                let source_y = (pixel.1 + i / SIDE).checked_sub(RADIUS);
+                match (source_x, source_y) {
+                    (Some(x), Some(y)) if rect.contains(&Rect::new(x, y, 1, 1)) => {
+                        channel.apply(image[(y - rect.y) * rect.width + x - rect.x])
+                    }
+                    _ => 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        let detail = self.detail.as_ref().filter(|detail| {
+            self.mode == Mode::Detail && (detail.canvas, detail.centre) == (canvas, pixel)
+        });
+
+        // beside the cursor, on whichever side has room, and inside the window if it fits
+        let cell = MAGNIFICATION * scale;
+        let size = SIDE * cell;
+        let gap = GAP * scale as isize;
+        let place = |at: isize, len: usize| {
+            let max = len as isize - size as isize;
+            let beside = if at + gap <= max {
+                at + gap
+            } else {
+                at - gap - size as isize
+            };
+            beside.min(max).max(0)
+        };
+        let (left, top) = (place(x, frame.width), place(y, frame.height));
+
+        for (i, &color) in enlarged.iter().enumerate() {
+            let cell_x = left + ((i % SIDE) * cell) as isize;
+            let cell_y = top + ((i / SIDE) * cell) as isize;
+            frame.fill(cell_x, cell_y, cell, cell, color);
+        }
+        if let Some(detail) = detail {
+            let side = SIDE * DETAIL;
+            let sample = cell / DETAIL;
+            for (i, &color) in detail.pixels.iter().enumerate() {
+                if color != UNRENDERED {
+                    let sample_x = left + ((i % side) * sample) as isize;
+                    let sample_y = top + ((i / side) * sample) as isize;
+                    frame.fill(sample_x, sample_y, sample, sample, channel.apply(color));
+                }
+            }
+        }
+
+        // a grid between the pixels
+        for i in 1..SIDE {
+            let offset = (i * cell) as isize;
+            frame.shade(left + offset, top, 1, size);
+            frame.shade(left, top + offset, size, 1);
+        }
+        frame.outline(left, top, size, size, 0xff00_0000);
+        let centre = (RADIUS * cell) as isize;
+        trace_panel::highlight(frame, left + centre, top + centre, cell);
+    }
+}
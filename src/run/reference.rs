@@ -0,0 +1,138 @@
+//! Differences from a reference render, shown in place of the image with `d`
+
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::Instant,
+};
+
+use super::RESAMPLE_INTERVAL;
+use crate::{diff::Diff, image::Image, Canvas, Rect};
+
+/// Differences from the reference, for `rect` of the canvas, with them amplified in the
+/// softbuffer format
+struct Compared {
+    /// Counts the comparisons started, so a late one cannot replace a newer one
+    generation: u64,
+    rect: Rect,
+    diff: Diff,
+    pixels: Vec<u32>,
+}
+
+impl Compared {
+    fn new(generation: u64, image: &[u32], rect: Rect, reference: &Image) -> Self {
+        let diff = Diff::rendered(image, rect, reference);
+        let pixels = diff.to_softbuffer(diff.gain());
+        Self {
+            generation,
+            rect,
+            diff,
+            pixels,
+        }
+    }
+}
+
+pub struct Reference {
+    image: Arc<Image>,
+    compared: Option<Compared>,
+    /// Whether the canvas is the size of the reference, so it can be compared
+    comparable: bool,
+    /// When the latest comparison was started, and the part of the canvas it covers
+    started: Option<(Instant, Rect)>,
+    generation: u64,
+    /// Whether a comparison is underway on another thread
+    comparing: bool,
+    /// Whether the render has changed since the latest comparison was started
+    pub stale: bool,
+    tx: Sender<Compared>,
+    rx: Receiver<Compared>,
+}
+
+impl Reference {
+    pub fn new(image: Arc<Image>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            image,
+            compared: None,
+            comparable: true,
+            started: None,
+            generation: 0,
+            comparing: false,
+            stale: true,
+            tx,
+            rx,
+        }
+    }
+
+    /// Receives finished comparisons, and compares `image`, holding `rect` of `canvas`, with the
+    /// reference again if it has changed. While `rendering`, that happens on another thread at
+    /// most every [`RESAMPLE_INTERVAL`], so the viewer stays responsive. Once the render is
+    /// finished, it happens at once, so the final differences can be reported.
+    /// Returns whether the differences changed. Only a canvas the size of the reference can be
+    /// compared.
+    pub fn update(&mut self, image: &[u32], rect: Rect, canvas: Canvas, rendering: bool) -> bool {
+        self.comparable = (canvas.width, canvas.height) == (self.image.width, self.image.height);
+        if !self.comparable {
+            self.started = None;
+            return self.compared.take().is_some();
+        }
+        let mut changed = false;
+        if let Some(compared) = self.rx.try_iter().last() {
+            self.comparing = false;
+            let newer = self
+                .compared
+                .as_ref()
+                .is_none_or(|shown| shown.generation < compared.generation);
+            if compared.rect == rect && newer {
+                self.compared = Some(compared);
+                changed = true;
+            }
+        }
+        if self
+            .started
+            .is_some_and(|(_, started_for)| started_for != rect)
+        {
+            // the differences for another part of the canvas would be misplaced
+            changed |= self.compared.take().is_some();
+            self.started = None;
+        }
+        let recent = self
+            .started
+            .is_some_and(|(at, _)| at.elapsed() < RESAMPLE_INTERVAL);
+        if self.started.is_some() && !(self.stale && !(rendering && recent)) {
+            return changed;
+        }
+        self.started = Some((Instant::now(), rect));
+        self.stale = false;
+        if !rendering {
+            self.generation += 1;
+            self.compared = Some(Compared::new(self.generation, image, rect, &self.image));
+            return true;
+        }
+        if !self.comparing {
+            self.comparing = true;
+            self.generation += 1;
+            let generation = self.generation;
+            let (image, reference, tx) = (image.to_vec(), self.image.clone(), self.tx.clone());
+            std::thread::spawn(move || {
+                // the viewer may have closed in the meantime
+                let _ = tx.send(Compared::new(generation, &image, rect, &reference));
+            });
+        } else {
+            // compare again once the comparison underway arrives
+            self.stale = true;
+        }
+        changed
+    }
+
+    /// The latest differences and their amplified pixels, or why there are none to show
+    pub fn diff(&self) -> Result<(&Diff, &[u32]), &'static str> {
+        match &self.compared {
+            Some(compared) => Ok((&compared.diff, compared.pixels.as_slice())),
+            None if !self.comparable => Err("only at the starting zoom"),
+            None => Err("comparing"),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Lookup tables between sRGB channel values and linear light, fast enough to use on every
+//! pixel of every frame
+
+use std::sync::LazyLock;
+
+use crate::sampling::{from_linear, to_linear};
+
+/// Steps of linear light in [`ENCODE`]
+const LEVELS: usize = 4096;
+
+static DECODE: LazyLock<[f64; 256]> =
+    LazyLock::new(|| std::array::from_fn(|c| to_linear(c as f64)));
+
+static ENCODE: LazyLock<Vec<u8>> = LazyLock::new(|| {
+    (0..=LEVELS)
+        .map(|level| from_linear(level as f64 / LEVELS as f64).round() as u8)
+        .collect()
+});
+
+/// Linear light in `0..=1` of an sRGB channel value
+pub fn decode(c: u8) -> f64 {
+    DECODE[c as usize]
+}
+
+/// sRGB channel value of linear light in `0..=1`
+pub fn encode(linear: f64) -> u8 {
+    ENCODE[(linear.clamp(0., 1.) * LEVELS as f64).round() as usize]
+}
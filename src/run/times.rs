@@ -0,0 +1,122 @@
+//! How long each pixel took to render, shown in place of the image with `p`
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    time::Instant,
+};
+
+use super::RESAMPLE_INTERVAL;
+use crate::{heatmap, render::UNRENDERED, softbuffer_color, Rect};
+
+/// The times of `rect` of the canvas on the colour ramp, in the softbuffer format
+struct Drawn {
+    rect: Rect,
+    pixels: Vec<u32>,
+    /// Range of the colour ramp, on the log scale of [`heatmap::log_seconds`]
+    range: Option<(f64, f64)>,
+}
+
+impl Drawn {
+    fn new(times: &[f32], rect: Rect) -> Self {
+        let values = times
+            .iter()
+            .map(|&seconds| match seconds {
+                seconds if seconds.is_nan() => f64::NAN,
+                seconds => heatmap::log_seconds(seconds as f64),
+            })
+            .collect::<Vec<_>>();
+        let range = heatmap::time_range(&values);
+        let ramp = range.unwrap_or((0., 0.));
+        let pixels = values
+            .iter()
+            .map(|&value| match value {
+                value if value.is_nan() => UNRENDERED,
+                value => softbuffer_color(heatmap::colour(value, ramp)),
+            })
+            .collect();
+        Self {
+            rect,
+            pixels,
+            range,
+        }
+    }
+}
+
+pub struct TimeMap {
+    drawn: Option<Drawn>,
+    /// When the latest drawing was started, and the part of the canvas it covers
+    started: Option<(Instant, Rect)>,
+    /// Whether a drawing is underway on another thread
+    drawing: bool,
+    /// Whether the times have changed since the latest drawing was started
+    pub stale: bool,
+    tx: Sender<Drawn>,
+    rx: Receiver<Drawn>,
+}
+
+impl Default for TimeMap {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            drawn: None,
+            started: None,
+            drawing: false,
+            stale: true,
+            tx,
+            rx,
+        }
+    }
+}
+
+impl TimeMap {
+    /// Receives finished drawings, and draws `times`, the seconds each pixel of `rect` took to
+    /// render or NaN where it is not rendered yet, again on another thread if they have changed,
+    /// at most every [`RESAMPLE_INTERVAL`] while `rendering`. Returns whether the pixels changed.
+    pub fn update(&mut self, times: &[f32], rect: Rect, rendering: bool) -> bool {
+        let mut changed = false;
+        if let Some(drawn) = self.rx.try_iter().last() {
+            self.drawing = false;
+            if drawn.rect == rect {
+                self.drawn = Some(drawn);
+                changed = true;
+            }
+        }
+        if self
+            .started
+            .is_some_and(|(_, started_for)| started_for != rect)
+        {
+            // the times of another part of the canvas would be misplaced
+            changed |= self.drawn.take().is_some();
+            self.started = None;
+        }
+        let recent = self
+            .started
+            .is_some_and(|(at, _)| rendering && at.elapsed() < RESAMPLE_INTERVAL);
+        let due = self.started.is_none() || (self.stale && !recent);
+        // not until the times are being recorded, see `RenderOptions::timed`
+        if !due || self.drawing || times.len() != rect.len() {
+            return changed;
+        }
+        self.started = Some((Instant::now(), rect));
+        self.stale = false;
+        self.drawing = true;
+        let (times, tx) = (times.to_vec(), self.tx.clone());
+        std::thread::spawn(move || {
+            // the viewer may have closed in the meantime
+            let _ = tx.send(Drawn::new(&times, rect));
+        });
+        changed
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        self.drawn.as_ref().map_or(&[], |drawn| &drawn.pixels)
+    }
+
+    /// Seconds at the ends of the colour ramp, once any pixels are rendered
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.drawn
+            .as_ref()
+            .and_then(|drawn| drawn.range)
+            .map(|(min, max)| (10f64.powf(min), 10f64.powf(max)))
+    }
+}
@@ -0,0 +1,222 @@
+//! A panel for exploring the [`Trace`] of the last clicked pixel as a collapsible tree
+
+use std::collections::HashSet;
+
+use winit::keyboard::{Key, NamedKey};
+
+use crate::{
+    overlay::{self, Frame, TEXT_COLOR},
+    trace::{Call, Trace},
+    Canvas,
+};
+
+/// Fraction of the window's width taken by the panel
+const WIDTH: f64 = 0.45;
+
+/// Rows moved by one step of the mouse wheel
+pub const SCROLL_STEP: isize = 3;
+
+/// Rows moved by Page Up and Page Down
+const PAGE_ROWS: isize = 20;
+
+/// Lines above the tree
+const HEADER_LINES: usize = 3;
+
+const SELECTED_COLOR: u32 = 0xff40_4060;
+
+/// Indices of a call among its siblings, from the top level down
+type Path = Vec<usize>;
+
+/// A call shown in the panel
+struct Row<'a> {
+    path: Path,
+    call: &'a Call,
+}
+
+pub struct TracePanel {
+    pub trace: Trace,
+    /// The canvas the traced pixel was clicked on
+    pub canvas: Canvas,
+    expanded: HashSet<Path>,
+    /// Index of the selected row
+    selected: usize,
+    /// First row shown
+    scroll: usize,
+}
+
+impl TracePanel {
+    pub fn new(trace: Trace, canvas: Canvas) -> Self {
+        Self {
+            trace,
+            canvas,
+            expanded: HashSet::new(),
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    /// Left edge of the panel, which runs down the right side of a window `width` wide
+    pub fn left(width: usize) -> usize {
+        width - (width as f64 * WIDTH) as usize
+    }
+
+    /// The calls currently shown: the top level, and the calls of every expanded call
+    fn rows(&self) -> Vec<Row<'_>> {
+        fn walk<'a>(
+            calls: &'a [Call],
+            path: &mut Path,
+            expanded: &HashSet<Path>,
+            rows: &mut Vec<Row<'a>>,
+        ) {
+            for (i, call) in calls.iter().enumerate() {
+                path.push(i);
+                rows.push(Row {
+                    path: path.clone(),
+                    call,
+                });
+                if expanded.contains(path) {
+                    walk(&call.calls, path, expanded, rows);
+                }
+                path.pop();
+            }
+        }
+        let mut rows = Vec::new();
+        walk(
+            &self.trace.calls,
+            &mut Vec::new(),
+            &self.expanded,
+            &mut rows,
+        );
+        rows
+    }
+
+    /// Moves the selection by `rows`, up if negative
+    pub fn move_by(&mut self, rows: isize) {
+        let max = self.rows().len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(rows).min(max);
+    }
+
+    /// Handles a key press, returning whether the panel used it
+    pub fn key(&mut self, key: &Key) -> bool {
+        let rows = self.rows();
+        let Some(Row { path, call }) = rows.get(self.selected) else {
+            return false;
+        };
+        let (path, has_calls, len) = (path.clone(), !call.calls.is_empty(), rows.len());
+        let parent = path
+            .split_last()
+            .and_then(|(_, parent)| rows.iter().position(|row| row.path == parent));
+        let is_expanded = self.expanded.contains(&path);
+        match key {
+            Key::Named(NamedKey::ArrowUp) => self.move_by(-1),
+            Key::Named(NamedKey::ArrowDown) => self.move_by(1),
+            Key::Named(NamedKey::PageUp) => self.move_by(-PAGE_ROWS),
+            Key::Named(NamedKey::PageDown) => self.move_by(PAGE_ROWS),
+            Key::Named(NamedKey::Home) => self.selected = 0,
+            Key::Named(NamedKey::End) => self.selected = len - 1,
+            Key::Named(NamedKey::ArrowRight) if has_calls && !is_expanded => {
+                self.expanded.insert(path);
+            }
+            // step into an expanded call
+            Key::Named(NamedKey::ArrowRight) => {
+                if has_calls {
+                    self.move_by(1);
+                }
+            }
+            Key::Named(NamedKey::ArrowLeft) if is_expanded => {
+                self.expanded.remove(&path);
+            }
+            // step out to the parent
+            Key::Named(NamedKey::ArrowLeft) => {
+                if let Some(parent) = parent {
+                    self.selected = parent;
+                }
+            }
+            Key::Named(NamedKey::Enter | NamedKey::Space) => {
+                if is_expanded {
+                    self.expanded.remove(&path);
+                } else if has_calls {
+                    self.expanded.insert(path);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draws the panel, scrolling it so the selected row is shown
+    pub fn draw(&mut self, frame: &mut Frame, scale: usize) {
+        let left = Self::left(frame.width);
+        let width = frame.width - left;
+        frame.shade(left as isize, 0, width, frame.height);
+
+        let padding = overlay::padding(scale);
+        let line_height = overlay::line_height(scale);
+        let columns = width.saturating_sub(2 * padding) / overlay::advance(scale);
+        let lines = frame.height.saturating_sub(2 * padding) / line_height;
+        let shown = lines.saturating_sub(HEADER_LINES).max(1);
+
+        self.scroll = self
+            .scroll
+            .clamp((self.selected + 1).saturating_sub(shown), self.selected);
+        let rows = self.rows();
+
+        let Trace { m, n, rgb, .. } = &self.trace;
+        let (r, g, b) = rgb;
+        let header = [
+            format!("@ m = {m:.2}, n = {n:.2}"),
+            format!("=> rgb({r}, {g}, {b})"),
+            format!(
+                "{selected}/{len}  arrows, enter, esc to close",
+                selected = self.selected + 1,
+                len = rows.len()
+            ),
+        ];
+        let body = rows.iter().enumerate().skip(self.scroll).map(|(i, row)| {
+            let marker = if row.call.calls.is_empty() {
+                ' '
+            } else if self.expanded.contains(&row.path) {
+                '-'
+            } else {
+                '+'
+            };
+            let line = format!(
+                "{indent}{marker} {call}  {share:.1}%",
+                indent = "  ".repeat(row.path.len() - 1),
+                call = row.call,
+                share = row.call.share * 100.
+            );
+            (i == self.selected, line)
+        });
+        let lines = header.into_iter().map(|line| (false, line)).chain(body);
+        for (i, (selected, line)) in lines.take(HEADER_LINES + shown).enumerate() {
+            let y = (padding + i * line_height) as isize;
+            if selected {
+                frame.fill(
+                    left as isize,
+                    y - scale as isize,
+                    width,
+                    line_height,
+                    SELECTED_COLOR,
+                );
+            }
+            let line = line.chars().take(columns).collect::<String>();
+            frame.text((left + padding) as isize, y, &line, scale, TEXT_COLOR);
+        }
+    }
+}
+
+/// Outlines the pixel at `(x, y)` of the window, which is `size` pixels square,
+/// in black and white so it stands out against any colour
+pub fn highlight(frame: &mut Frame, x: isize, y: isize, size: usize) {
+    let gap = 2;
+    for (inset, color) in [(gap + 2, 0xff00_0000), (gap + 1, TEXT_COLOR)] {
+        frame.outline(
+            x - inset,
+            y - inset,
+            size + 2 * inset as usize,
+            size + 2 * inset as usize,
+            color,
+        );
+    }
+}
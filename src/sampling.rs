@@ -0,0 +1,201 @@
+use std::{fmt, str::FromStr};
+
+use crate::{Art, Canvas};
+
+/// Most samples along each side of a pixel's grid, so at most 4096 samples per pixel
+pub const MAX_SAMPLES: usize = 64;
+
+/// Where to sample the artwork within each pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sampling {
+    /// One sample at the centre of the pixel
+    #[default]
+    Single,
+    /// `n` x `n` samples at the centres of a regular grid, with `n` up to [`MAX_SAMPLES`]
+    Grid(usize),
+    /// `n` x `n` samples, each placed at random within its cell of the grid,
+    /// with `n` up to [`MAX_SAMPLES`]
+    Jittered(usize),
+}
+
+impl Sampling {
+    /// Colour of the pixel `(x, y)` of `canvas`.
+    /// Multiple samples are averaged in linear light before quantisation,
+    /// so that edges blend as they would optically.
+    pub fn draw<Artwork: Art>(&self, canvas: &Canvas, x: usize, y: usize) -> (u8, u8, u8) {
+        if self.is_single() {
+            let (m, n) = canvas.mn::<Artwork>(x as f64 + 0.5, y as f64 + 0.5);
+            return Artwork::draw(m, n);
+        }
+        let (r, g, b) = self.channels::<Artwork>(canvas, x, y);
+        quantise(r, g, b)
+    }
+
+    /// Channel values of the pixel `(x, y)` of `canvas` before quantisation,
+    /// see [`Art::channels_xy`]. Multiple samples are averaged in linear light.
+    pub fn channels<Artwork: Art>(&self, canvas: &Canvas, x: usize, y: usize) -> (f64, f64, f64) {
+        if self.is_single() {
+            let (m, n) = canvas.mn::<Artwork>(x as f64 + 0.5, y as f64 + 0.5);
+            return Artwork::channels(m, n);
+        }
+        let samples = self.samples();
+        let jitter = matches!(self, Sampling::Jittered(_));
+
+        let mut seed = (y * canvas.width + x) as u64;
+        let mut sum = (0., 0., 0.);
+        for j in 0..samples {
+            for i in 0..samples {
+                let (dx, dy) = if jitter {
+                    (random(&mut seed), random(&mut seed))
+                } else {
+                    (0.5, 0.5)
+                };
+                let px = x as f64 + (i as f64 + dx) / samples as f64;
+                let py = y as f64 + (j as f64 + dy) / samples as f64;
+                let (m, n) = canvas.mn::<Artwork>(px, py);
+                let (r, g, b) = Artwork::channels(m, n);
+                sum.0 += to_linear(r);
+                sum.1 += to_linear(g);
+                sum.2 += to_linear(b);
+            }
+        }
+
+        let count = samples
+            .checked_mul(samples)
+            .expect("samples are at most MAX_SAMPLES") as f64;
+        (
+            from_linear(sum.0 / count),
+            from_linear(sum.1 / count),
+            from_linear(sum.2 / count),
+        )
+    }
+
+    /// Whether the pixel is sampled once at its centre
+    fn is_single(&self) -> bool {
+        matches!(self, Sampling::Single | Sampling::Grid(0 | 1))
+    }
+
+    /// Samples along each side of the pixel's grid, at most [`MAX_SAMPLES`]
+    fn samples(&self) -> usize {
+        match *self {
+            Sampling::Single => 1,
+            Sampling::Grid(n) | Sampling::Jittered(n) => n.clamp(1, MAX_SAMPLES),
+        }
+    }
+}
+
+impl fmt::Display for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sampling::Single => write!(f, "single"),
+            Sampling::Grid(n) => write!(f, "grid:{n}"),
+            Sampling::Jittered(n) => write!(f, "jittered:{n}"),
+        }
+    }
+}
+
+impl FromStr for Sampling {
+    type Err = String;
+
+    /// Parses `single`, `grid:<n>` or `jittered:<n>`, with `n` from 1 to [`MAX_SAMPLES`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("single:") {
+            return Err(format!("single takes no number of samples, found: {s}"));
+        }
+        let (kind, n) = s.split_once(':').unwrap_or((s, "1"));
+        let n = n
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=MAX_SAMPLES).contains(n))
+            .ok_or_else(|| {
+                format!("expected a number of samples from 1 to {MAX_SAMPLES}, found: {n}")
+            })?;
+        match kind {
+            "single" => Ok(Sampling::Single),
+            "grid" => Ok(Sampling::Grid(n)),
+            "jittered" => Ok(Sampling::Jittered(n)),
+            _ => Err(format!(
+                "expected single, grid:<n> or jittered:<n>, found: {s}"
+            )),
+        }
+    }
+}
+
+/// Same rounding as the artworks' own `rgb`, saturating at `0` and `255`
+#[inline(always)]
+pub fn quantise(r: f64, g: f64, b: f64) -> (u8, u8, u8) {
+    (r.round() as u8, g.round() as u8, b.round() as u8)
+}
+
+/// Decodes an sRGB channel value in `0..=255` to linear light in `0..=1`.
+/// Values outside the range are decoded too, so that overshoot survives averaging and is only
+/// saturated by [`quantise`].
+#[inline(always)]
+pub(crate) fn to_linear(c: f64) -> f64 {
+    let c = c / 255.;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes linear light in `0..=1` as an sRGB channel value in `0..=255`
+#[inline(always)]
+pub(crate) fn from_linear(c: f64) -> f64 {
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    };
+    c * 255.
+}
+
+/// SplitMix64, mapped to `0..1`.
+/// Seeded per pixel so that jittered renders are reproducible.
+#[inline(always)]
+fn random(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_patterns() {
+        assert_eq!("single".parse(), Ok(Sampling::Single));
+        assert_eq!("grid:1".parse(), Ok(Sampling::Grid(1)));
+        assert_eq!("jittered:4".parse(), Ok(Sampling::Jittered(4)));
+        assert!("single:4".parse::<Sampling>().is_err());
+        assert!("grid:0".parse::<Sampling>().is_err());
+        assert!("grid:-1".parse::<Sampling>().is_err());
+        assert!("stratified:4".parse::<Sampling>().is_err());
+    }
+
+    #[test]
+    fn parses_up_to_the_most_samples() {
+        let most = format!("jittered:{MAX_SAMPLES}");
+        assert_eq!(most.parse(), Ok(Sampling::Jittered(MAX_SAMPLES)));
+        assert_eq!(most.parse::<Sampling>().unwrap().to_string(), most);
+        for too_many in [
+            format!("grid:{}", MAX_SAMPLES + 1),
+            "jittered:5000000000".to_string(),
+            format!("grid:{}", usize::MAX),
+        ] {
+            let error = too_many.parse::<Sampling>().unwrap_err();
+            assert!(error.contains(&MAX_SAMPLES.to_string()), "{error}");
+        }
+    }
+
+    #[test]
+    fn clamps_samples_built_directly() {
+        assert_eq!(Sampling::Grid(usize::MAX).samples(), MAX_SAMPLES);
+        assert_eq!(Sampling::Jittered(0).samples(), 1);
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt;
+
+use crate::{
+    track::{self, set_should_track, with_nudge, with_stack},
+    Art,
+};
+
+/// The tree of tracked calls that produced the colour of a single pixel
+pub struct Trace {
+    pub m: f64,
+    pub n: f64,
+    /// Top-level calls, in the order they were made
+    pub calls: Vec<Call>,
+    pub rgb: (u8, u8, u8),
+}
+
+/// One tracked call, with the tracked calls made while computing it
+pub struct Call {
+    pub name: &'static str,
+    pub args: Vec<(&'static str, Value)>,
+    pub result: Value,
+    pub calls: Vec<Call>,
+    /// Fraction of the final colour this call accounts for, to first order: how much the
+    /// channel values passed to `rgb` change when its result is nudged, times its result, over
+    /// the sum of the channel values. Calls whose results are added together share the colour
+    /// between them, and `rgb` accounts for all of it.
+    pub share: f64,
+}
+
+/// An argument or result of a [`Call`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Usize(usize),
+    F64(f64),
+    Rgb(u8, u8, u8),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Usize(value) => write!(f, "{value}"),
+            Value::F64(value) => write!(f, "{value:.3}"),
+            Value::Rgb(r, g, b) => write!(f, "({r}, {g}, {b})"),
+        }
+    }
+}
+
+impl fmt::Display for Call {
+    /// e.g. `F(x = 0.500) = 12.345`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{name}(", name = self.name)?;
+        for (i, (arg, value)) in self.args.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            write!(f, "{separator}{arg} = {value}")?;
+        }
+        write!(f, ") = {result}", result = self.result)
+    }
+}
+
+impl Trace {
+    /// Each call with its nesting depth, depth first
+    pub fn lines(&self) -> Vec<(usize, String)> {
+        fn walk(calls: &[Call], depth: usize, lines: &mut Vec<(usize, String)>) {
+            for call in calls {
+                lines.push((depth, call.to_string()));
+                walk(&call.calls, depth + 1, lines);
+            }
+        }
+        let mut lines = Vec::new();
+        walk(&self.calls, 0, &mut lines);
+        lines
+    }
+
+    pub fn print(&self) {
+        println!("@ m = {m}, n = {n}", m = self.m, n = self.n);
+        for (depth, line) in self.lines() {
+            println!("  {indent}{line}", indent = "  ".repeat(depth));
+        }
+        let (r, g, b) = self.rgb;
+        println!("=> rgb({r}, {g}, {b})");
+    }
+}
+
+/// Draws the pixel at `(m, n)` while recording every tracked call on this thread
+pub fn trace<Artwork: Art>(m: f64, n: f64) -> Trace {
+    set_should_track(true);
+    let rgb = Artwork::draw(m, n);
+    set_should_track(false);
+
+    let mut calls = Vec::new();
+    with_stack(|stack| {
+        // calls that have started but not finished, innermost last
+        let mut open = Vec::<Call>::new();
+        for item in stack.drain(..) {
+            let result = match item {
+                track::Item::Start(name) => {
+                    open.push(Call {
+                        name,
+                        args: Vec::new(),
+                        result: Value::F64(f64::NAN),
+                        calls: Vec::new(),
+                        share: 0.,
+                    });
+                    continue;
+                }
+                track::Item::ArgUsize(arg, value) => {
+                    let call = open.last_mut().expect("arguments should follow a start");
+                    call.args.push((arg, Value::Usize(value)));
+                    continue;
+                }
+                track::Item::ArgF64(arg, value) => {
+                    let call = open.last_mut().expect("arguments should follow a start");
+                    call.args.push((arg, Value::F64(value)));
+                    continue;
+                }
+                track::Item::ArgEnd => continue,
+                track::Item::FinishRgb(r, g, b) => Value::Rgb(r, g, b),
+                track::Item::FinishF64(output) => Value::F64(output),
+            };
+            let mut call = open.pop().expect("a finish should follow a start");
+            call.result = result;
+            match open.last_mut() {
+                Some(parent) => parent.calls.push(call),
+                None => calls.push(call),
+            }
+        }
+    });
+
+    share_colour::<Artwork>(m, n, &mut calls);
+    Trace { m, n, calls, rgb }
+}
+
+/// Works out [`Call::share`] for `calls`, traced at `(m, n)`, and everything below them
+fn share_colour<Artwork: Art>(m: f64, n: f64, calls: &mut [Call]) {
+    fn walk(calls: &mut [Call], next: &mut usize, share: &impl Fn(usize, f64) -> f64) {
+        for call in calls {
+            // calls are numbered in the order they start, so parents before their calls
+            call.share = match call.result {
+                Value::F64(value) => share(*next, value),
+                // `rgb`, whose result is the colour
+                _ => 1.,
+            };
+            *next += 1;
+            walk(&mut call.calls, next, share);
+        }
+    }
+
+    let channels = |(r, g, b): (f64, f64, f64)| [r, g, b];
+    let before = channels(Artwork::channels(m, n));
+    let total = before.iter().map(|channel| channel.abs()).sum::<f64>();
+    let share = |call, value: f64| {
+        if total == 0. || !value.is_finite() {
+            return 0.;
+        }
+        let step = f64::EPSILON.sqrt() * value.abs().max(1.);
+        let after = channels(with_nudge(call, step, || Artwork::channels(m, n)));
+        let change = before
+            .iter()
+            .zip(after)
+            .map(|(before, after)| ((after - before) / step * value).abs())
+            .sum::<f64>();
+        change / total
+    };
+    walk(calls, &mut 0, &share);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::art::{strawberries, sunflower_field};
+
+    fn shares(trace: &Trace) -> Vec<(&'static str, f64)> {
+        fn walk(calls: &[Call], shares: &mut Vec<(&'static str, f64)>) {
+            for call in calls {
+                shares.push((call.name, call.share));
+                walk(&call.calls, shares);
+            }
+        }
+        let mut shares = Vec::new();
+        walk(&trace.calls, &mut shares);
+        shares
+    }
+
+    fn assert_close(shares: &[(&str, f64)], expected: &[(&str, f64)]) {
+        assert_eq!(shares.len(), expected.len(), "{shares:?}");
+        for (&(name, share), &(expected_name, expected_share)) in shares.iter().zip(expected) {
+            assert_eq!(name, expected_name);
+            assert!(
+                (share - expected_share).abs() < 1e-6,
+                "{name}: {share} instead of {expected_share} in {shares:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn channels_share_the_colour() {
+        let trace = trace::<strawberries::Artwork>(1000., 600.);
+        let (r, g, b) = strawberries::Artwork::channels(1000., 600.);
+        let (r, g, b) = (r / (r + g + b), g / (r + g + b), b / (r + g + b));
+        assert_close(
+            &shares(&trace),
+            &[
+                ("H", r),
+                ("F", r),
+                ("H", g),
+                ("F", g),
+                ("H", b),
+                ("F", b),
+                ("rgb", 1.),
+            ],
+        );
+        let channels = shares(&trace)
+            .iter()
+            .filter(|(name, _)| *name == "F")
+            .map(|(_, share)| share)
+            .sum::<f64>();
+        assert!((channels - 1.).abs() < 1e-6, "{channels}");
+    }
+
+    #[test]
+    fn nested_calls_share_their_parents_colour() {
+        // `H` is `A` plus the sky, which is hidden here, and `F` is nearly linear for each channel
+        let trace = trace::<sunflower_field::Artwork>(1000., 600.);
+        let (r, g, _) = sunflower_field::Artwork::channels(1000., 600.);
+        let (r, g) = (r / (r + g), g / (r + g));
+        assert_close(
+            &shares(&trace),
+            &[
+                ("H", r),
+                ("A", r),
+                ("F", r),
+                ("H", g),
+                ("A", g),
+                ("F", g),
+                ("H", 0.),
+                ("A", 0.),
+                ("F", 0.),
+                ("rgb", 1.),
+            ],
+        );
+    }
+
+    #[test]
+    fn saturated_calls_have_no_share() {
+        // `F` saturates red at 255, so nudging `H` does not change the colour
+        let trace = trace::<sunflower_field::Artwork>(300., 900.);
+        let (r, g, _) = sunflower_field::Artwork::channels(300., 900.);
+        let (r, g) = (r / (r + g), g / (r + g));
+        assert_close(
+            &shares(&trace),
+            &[
+                ("H", 0.),
+                ("A", 0.),
+                ("F", r),
+                ("H", g),
+                ("A", g),
+                ("F", g),
+                ("H", 0.),
+                ("A", 0.),
+                ("F", 0.),
+                ("rgb", 1.),
+            ],
+        );
+    }
+}
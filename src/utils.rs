@@ -236,6 +236,13 @@ pub fn softbuffer_color(rgb: (u8, u8, u8)) -> u32 {
     u32::from_be_bytes([255, r, g, b])
 }
 
+/// Inverse of [`softbuffer_color`], discarding the alpha channel
+#[inline(always)]
+pub fn rgb_from_softbuffer_color(color: u32) -> (u8, u8, u8) {
+    let [_, r, g, b] = color.to_be_bytes();
+    (r, g, b)
+}
+
 pub fn xy_from_index(width: usize, index: usize) -> (usize, usize) {
     let x = index % width;
     let y = index / width;
@@ -38,6 +38,24 @@ impl From<f64> for Item {
     }
 }
 
+/// Result of a tracked function that [`with_nudge`] can change
+pub trait Nudge {
+    fn nudge(self, by: f64) -> Self;
+}
+
+impl Nudge for f64 {
+    fn nudge(self, by: f64) -> Self {
+        self + by
+    }
+}
+
+impl Nudge for (u8, u8, u8) {
+    /// The colour is left alone, as nothing is computed from it
+    fn nudge(self, _by: f64) -> Self {
+        self
+    }
+}
+
 use std::cell::Cell;
 
 use super::with_local_cell;
@@ -45,6 +63,10 @@ use super::with_local_cell;
 thread_local! {
     static STACK: Cell<Vec<Item>> = Cell::new(Vec::with_capacity(400_000));
     static SHOULD_TRACK: Cell<bool> = const { Cell::new(false) };
+    /// Number of tracked calls started since tracking was turned on
+    static CALLS: Cell<usize> = const { Cell::new(0) };
+    /// Which tracked call [`with_nudge`] changes the result of, and by how much
+    static NUDGE: Cell<Option<(usize, f64)>> = const { Cell::new(None) };
 }
 
 #[macro_export]
@@ -53,18 +75,18 @@ macro_rules! track {
         #[allow(non_snake_case)]
         $(#[$attr])* $vis fn $name ( $($arg:$argty),* ) -> $outty {
 
-            use $crate::utils::track::{push_stack, should_track, Item};
+            use $crate::utils::track::{nudged, push_stack, should_track, start_call, Item};
 
             fn inner ( $($arg:$argty),* ) -> $outty { $($body)* }
 
             if should_track() {
                 // Push function name
-                push_stack(Item::Start(stringify!($name)));
+                let call = start_call(stringify!($name));
                 // Push arguments
                 $( push_stack((stringify!($arg), $arg).into()); )*
                 push_stack(Item::ArgEnd);
                 // Call function
-                let output = inner ( $($arg),* );
+                let output = nudged(call, inner ( $($arg),* ));
                 // Push output (finish)
                 push_stack(output.into());
                 output
@@ -81,11 +103,46 @@ pub fn push_stack(i: Item) {
     });
 }
 
+/// Pushes the start of a tracked call named `name`, returning its number, counting from zero
+/// in the order the calls start
+pub fn start_call(name: &'static str) -> usize {
+    push_stack(Item::Start(name));
+    with_local_cell(&CALLS, |calls| {
+        *calls += 1;
+        *calls - 1
+    })
+}
+
+/// `output` of tracked call number `call`, changed if [`with_nudge`] asks for it
+pub fn nudged<T: Nudge>(call: usize, output: T) -> T {
+    match NUDGE.get() {
+        Some((nudged, by)) if nudged == call => output.nudge(by),
+        _ => output,
+    }
+}
+
+/// Runs `f` with tracking on, but adding `by` to the result of tracked call number `call`,
+/// counting from zero in the order the calls start, to see how the result is used.
+/// The calls tracked meanwhile are discarded.
+pub fn with_nudge<O>(call: usize, by: f64, f: impl FnOnce() -> O) -> O {
+    NUDGE.set(Some((call, by)));
+    set_should_track(true);
+    let output = f();
+    set_should_track(false);
+    NUDGE.set(None);
+    with_stack(|stack| stack.clear());
+    output
+}
+
 pub fn with_stack<O>(f: impl FnOnce(&mut Vec<Item>) -> O) -> O {
     with_local_cell(&STACK, f)
 }
 
+/// Turning tracking on numbers the calls from zero again, see [`start_call`]
 pub fn set_should_track(track: bool) {
+    if track {
+        CALLS.set(0);
+    }
     with_local_cell(&SHOULD_TRACK, |should_track| *should_track = track);
 }
 